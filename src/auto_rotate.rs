@@ -0,0 +1,146 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use egui::{Context, Id};
+use log::{debug, error, warn};
+
+use crate::input::{find_device, InputDevice, EV_MSC, MSC_RAW};
+use crate::rotation::{self, Rotation};
+
+// What the g-sensor of NTX boards (Kobo Libra, Sage...) sends as MSC_RAW values when the
+// device is turned. Lying face up or face down (0x1b, 0x1c) isn't an orientation
+const GSENSOR_PORTRAIT_DOWN: i32 = 0x17;
+const GSENSOR_PORTRAIT_UP: i32 = 0x18;
+const GSENSOR_LANDSCAPE_RIGHT: i32 = 0x19;
+const GSENSOR_LANDSCAPE_LEFT: i32 = 0x1a;
+
+#[derive(Clone, Debug)]
+pub struct AutoRotateOptions {
+    /// The input device of the orientation sensor. Found through its capabilities if not set
+    pub device: Option<PathBuf>,
+    /// How long the device has to stay in an orientation before the screen follows.
+    /// Redrawing the whole e-ink screen is slow, so this shouldn't be too short
+    pub debounce: Duration,
+    /// The rotation to use when the device is upright, turned clockwise, upside down and
+    /// turned counter clockwise. Depends on how the panel is mounted
+    pub rotations: [Rotation; 4],
+}
+
+impl Default for AutoRotateOptions {
+    fn default() -> Self {
+        Self {
+            device: None,
+            debounce: Duration::from_millis(600),
+            rotations: [
+                Rotation::Deg0,
+                Rotation::Deg270,
+                Rotation::Deg180,
+                Rotation::Deg90,
+            ],
+        }
+    }
+}
+
+type Veto = Arc<dyn Fn(Rotation) -> bool + Send + Sync>;
+
+fn locked_id() -> Id {
+    Id::new("egui_fbink_orientation_locked")
+}
+
+fn veto_id() -> Id {
+    Id::new("egui_fbink_rotation_veto")
+}
+
+/// Stops auto-rotation from following the device, `set_rotation` still works
+pub fn lock_orientation(ctx: &Context, locked: bool) {
+    ctx.data_mut(|data| data.insert_temp(locked_id(), locked));
+}
+
+/// Asked before every automatic rotation, returning false keeps the current one
+pub fn set_rotation_veto(ctx: &Context, veto: impl Fn(Rotation) -> bool + Send + Sync + 'static) {
+    let veto: Veto = Arc::new(veto);
+    ctx.data_mut(|data| data.insert_temp(veto_id(), veto));
+}
+
+/// Follows the orientation sensor in a background thread, until it goes away
+pub(crate) fn spawn(ctx: Context, options: AutoRotateOptions) {
+    let Some(path) = options.device.clone().or_else(|| find_device("msc", MSC_RAW)) else {
+        warn!("No orientation sensor found, auto-rotation is off");
+        return;
+    };
+    let mut device = match InputDevice::open(&path) {
+        Ok(device) => device,
+        Err(err) => {
+            error!("Failed to open orientation sensor {}: {}", path.display(), err);
+            return;
+        }
+    };
+
+    thread::Builder::new()
+        .name("auto-rotate".to_owned())
+        .spawn(move || {
+            let mut pending: Option<(Rotation, Instant)> = None;
+            loop {
+                let timeout = pending.map(|(_, since)| options.debounce.saturating_sub(since.elapsed()));
+                if let Err(err) = device.wait(timeout) {
+                    error!("Failed to wait for {}: {}", device.path().display(), err);
+                    return;
+                }
+                let events = match device.read_events() {
+                    Ok(events) => events,
+                    Err(err) => {
+                        error!("Failed to read {}: {}", device.path().display(), err);
+                        return;
+                    }
+                };
+                for event in events {
+                    if event.type_ != EV_MSC || event.code != MSC_RAW {
+                        continue;
+                    }
+                    let rotation = match event.value {
+                        GSENSOR_PORTRAIT_UP => options.rotations[0],
+                        GSENSOR_LANDSCAPE_RIGHT => options.rotations[1],
+                        GSENSOR_PORTRAIT_DOWN => options.rotations[2],
+                        GSENSOR_LANDSCAPE_LEFT => options.rotations[3],
+                        _ => continue,
+                    };
+                    if pending.map(|(pending, _)| pending) != Some(rotation) {
+                        pending = Some((rotation, Instant::now()));
+                    }
+                }
+
+                if let Some((rotation, since)) = pending {
+                    if since.elapsed() >= options.debounce {
+                        pending = None;
+                        apply(&ctx, rotation);
+                    }
+                }
+            }
+        })
+        .expect("Failed to spawn the auto-rotate thread");
+}
+
+fn apply(ctx: &Context, rotation: Rotation) {
+    if rotation == rotation::rotation(ctx) {
+        return;
+    }
+    let (locked, veto) = ctx.data(|data| {
+        (
+            data.get_temp::<bool>(locked_id()).unwrap_or(false),
+            data.get_temp::<Veto>(veto_id()),
+        )
+    });
+    if locked {
+        debug!("Orientation is locked, staying out of {:?}", rotation);
+        return;
+    }
+    if veto.is_some_and(|veto| !veto(rotation)) {
+        debug!("The app vetoed rotating to {:?}", rotation);
+        return;
+    }
+    debug!("Device turned, rotating to {:?}", rotation);
+    rotation::set_rotation(ctx, rotation);
+    ctx.request_repaint();
+}
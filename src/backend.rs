@@ -2,31 +2,147 @@ use std::collections::HashMap;
 use std::hash::{BuildHasherDefault, Hash};
 use ::std::os::raw::c_int;
 use eframe::{App, IntegrationInfo};
-use egui::epaint::{text, ClippedShape};
-use egui::{output, Context, Event, FullOutput, RawInput, Rect, Shape, ViewportId, ViewportInfo};
+use egui::epaint::{text, ClippedShape, Mesh, Tessellator};
+use egui::{output, Context, Event, FullOutput, RawInput, Rect, Shape, ViewportCommand, ViewportId, ViewportInfo};
 use egui::{PointerButton, Pos2, TouchDeviceId, TouchId, TouchPhase, Vec2};
 use fbink_sys::*;
-use log::{debug, error};
+use log::{debug, error, warn};
 use raw_window_handle::HandleError;
 use std::fs;
+use std::path::PathBuf;
 use std::ptr::null;
 use std::sync::atomic::Ordering::SeqCst;
+use std::sync::mpsc::{self, Receiver};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::{ffi::CString, process::exit};
 
+use crate::clipboard::{self, Clipboard};
+use crate::color::rgb_to_gray;
+use crate::device::{self, Device};
+use crate::dither::{self, Dithering};
+use crate::dump;
 use crate::egui::EguiStuff;
+use crate::gesture::{self, GestureRecognizer};
 use crate::fbink::FBInkBackend;
+use crate::frontlight;
+use crate::image_loader;
+use crate::ink::{self, SharedInk};
+use crate::keyboard::Keyboard;
+use crate::night_mode;
+use crate::pen::{self, PenQueue, PenSample};
+use crate::platform;
+use crate::rotation;
+use crate::suspend::{self, SuspendOptions};
+use crate::theme;
+use crate::touch::TouchQueue;
+use crate::touch_profile;
+use crate::zoom;
+use crate::textures::TextureManager;
+
+const POWER_STATUS_INTERVAL: Duration = Duration::from_secs(30);
+/// How often FBInk checks the framebuffer wasn't changed under us
+const REINIT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Pulls the shapes of nested `Shape::Vec`s up in order, so every mesh among them is seen
+/// and drawn with its own texture instead of being tessellated into another one
+fn flatten_shapes(shapes: Vec<Shape>) -> Vec<Shape> {
+    let mut flat = Vec::with_capacity(shapes.len());
+    for shape in shapes {
+        match shape {
+            Shape::Vec(nested) => flat.extend(flatten_shapes(nested)),
+            shape => flat.push(shape),
+        }
+    }
+    flat
+}
+
+/// Where the runner gets input from, besides egui itself
+pub(crate) struct Inputs {
+    pub touches: Option<TouchQueue>,
+    pub gestures: GestureRecognizer,
+    pub pen: Option<PenQueue>,
+    pub ink: Option<SharedInk>,
+    pub keyboard: Option<Keyboard>,
+}
 
 pub struct AppRunner {
     fb: FBInkBackend,
     egui: EguiStuff,
+    textures: TextureManager,
+    /// Handed to egui with the next frame, like the replies to viewport commands
+    events: Vec<Event>,
+    /// Repaints requested by egui, the app or background threads, with their delay
+    repaint: Receiver<Duration>,
+    touches: Option<TouchQueue>,
+    gestures: GestureRecognizer,
+    pen: Option<PenQueue>,
+    /// The last stylus sample handed to egui
+    last_pen: PenSample,
+    ink: Option<SharedInk>,
+    keyboard: Option<Keyboard>,
+    clipboard: Clipboard,
+    /// Opens the links the app follows, unless it handles them itself
+    browser: Option<PathBuf>,
+    device: Device,
+    /// When the power status was last read
+    power_read: Option<Instant>,
+    /// When FBInk last looked at the framebuffer
+    reinit_at: Instant,
+    suspend: SuspendOptions,
+    /// The sleep screen is up
+    sleeping: bool,
+    /// Drawn instead of the app while sleeping, in 8-bit gray
+    cover: Option<Vec<u8>>,
+    /// The frontlight is turned off while sleeping, and back to this after
+    light_before_sleep: Option<u8>,
+    /// The sleep screen goes up this frame, the device is suspended once it's shown
+    enter_power_state: bool,
 }
 
 impl AppRunner {
-    pub(crate) fn new(egui: EguiStuff, fb: FBInkBackend) -> Self {
+    pub(crate) fn new(
+        egui: EguiStuff,
+        fb: FBInkBackend,
+        inputs: Inputs,
+        clipboard: Clipboard,
+        browser: Option<PathBuf>,
+        device: Device,
+        suspend: SuspendOptions,
+    ) -> Self {
+        let (repaint_sender, repaint) = mpsc::channel();
+        egui.ctx.set_request_repaint_callback(move |info| {
+            let _ = repaint_sender.send(info.delay);
+        });
+        let Inputs {
+            touches,
+            gestures,
+            pen,
+            ink,
+            keyboard,
+        } = inputs;
         let mut runner = Self {
             fb,
             egui,
+            textures: TextureManager::default(),
+            events: Vec::new(),
+            repaint,
+            touches,
+            gestures,
+            pen,
+            last_pen: PenSample::default(),
+            ink,
+            keyboard,
+            clipboard,
+            browser,
+            device,
+            power_read: None,
+            reinit_at: Instant::now(),
+            suspend,
+            sleeping: false,
+            cover: None,
+            light_before_sleep: None,
+            enter_power_state: false,
         };
         /*
         // gone?
@@ -38,8 +154,27 @@ impl AppRunner {
     }
 
     pub fn draw_shapes(&mut self, clipped_shapes: Vec<ClippedShape>) {
-        //let ppp = self.egui.pixel_per_point;
-        //debug!("draw shapes pixel per point: {}", ppp);
+        let pixels_per_point = self.egui.ctx.pixels_per_point();
+        //debug!("draw shapes pixel per point: {}", pixels_per_point);
+        let (font_tex_size, prepared_discs) = self.egui.ctx.fonts(|fonts| {
+            (
+                fonts.font_image_size(),
+                fonts.texture_atlas().lock().prepared_discs(),
+            )
+        });
+        let options = self.egui.ctx.tessellation_options(|options| *options);
+        // Feathering is what gives us anti-aliased edges
+        let mut tessellator =
+            Tessellator::new(pixels_per_point, options, font_tex_size, prepared_discs);
+
+        // Unmultiplied and usually translucent, eframe's default is nearly black at 70%.
+        // There is no window behind it here, so it goes over a white page
+        let clear_color = self.egui.app.clear_color(&self.egui.ctx.style().visuals);
+        let alpha = clear_color[3].clamp(0.0, 1.0);
+        let [r, g, b] = [0, 1, 2]
+            .map(|i| ((clear_color[i] * alpha + 1.0 - alpha) * 255.0).round() as u8);
+        self.fb.clear(rgb_to_gray(r, g, b));
+
         for shape in clipped_shapes {
             if shape.clip_rect.is_negative() {
                 error!("clip rect is negative");
@@ -47,58 +182,322 @@ impl AppRunner {
             } else {
                 // debug!("shape.0: {:?}", shape.0);
             }
+            let shape_clip_rect = shape.clip_rect;
+            tessellator.set_clip_rect(shape_clip_rect);
+            let mut mesh = Mesh::default();
             match shape.shape {
                 Shape::Noop => {}
                 Shape::Vec(vec) => {
                     debug!("Printing out vecs: {:?}", vec);
+                    for shape in flatten_shapes(vec) {
+                        match shape {
+                            // Meshes may use another texture, they can't be merged with the rest.
+                            // What came before them is drawn first to keep the order
+                            Shape::Mesh(child) => {
+                                let before = std::mem::take(&mut mesh);
+                                self.draw_mesh(&before, shape_clip_rect, pixels_per_point);
+                                self.draw_mesh(&child, shape_clip_rect, pixels_per_point);
+                            }
+                            Shape::Callback(_) => {}
+                            shape => tessellator.tessellate_shape(shape, &mut mesh),
+                        }
+                    }
                 }
                 Shape::Circle(circle) => {
                     debug!("Printing out circles: {:?}", circle);
+                    tessellator.tessellate_circle(circle, &mut mesh);
                 }
                 Shape::LineSegment {points, stroke} => {
                     debug!("Printing out points {:?} with strokes {:?}", points, stroke);
+                    tessellator.tessellate_line(points, stroke, &mut mesh);
                 }
                 Shape::Path(path) => {
                     debug!("Printing out path: {:?}", path);
-                    self.fb.draw_paths(path);
+                    tessellator.tessellate_path(&path, &mut mesh);
                 }
                 Shape::Rect(rect) => {
                     debug!("Printing out rectangle at {:?}", rect);
-                    self.fb.draw_rect(rect);
+                    tessellator.tessellate_rect(&rect, &mut mesh);
                 }
                 Shape::Text(text) => {
                     // debug!(
                     //     "Printing out string: {:?} at pos {:?} with size {:?}",
                     //     text.galley.text(), text.pos, text.galley.size()
                     // );
-                    self.fb.draw_text(text);
+                    tessellator.tessellate_text(&text, &mut mesh);
+                }
+                Shape::Mesh(mesg) => {
+                    mesh = mesg;
+                }
+                Shape::QuadraticBezier(qb) => {
+                    tessellator.tessellate_quadratic_bezier(qb, &mut mesh);
+                }
+                Shape::CubicBezier(cb) => {
+                    tessellator.tessellate_cubic_bezier(cb, &mut mesh);
+                }
+                Shape::Ellipse(ellipse) => {
+                    tessellator.tessellate_ellipse(ellipse, &mut mesh);
                 }
-                Shape::Mesh(mesg) => {}
-                Shape::QuadraticBezier(qb) => {}
-                Shape::CubicBezier(cb) => {},
-                Shape::Ellipse(ellipse) => {},
                 Shape::Callback(callback) => {}
             }
+            self.draw_mesh(&mesh, shape_clip_rect, pixels_per_point);
+        }
+
+        let (dithering, regions) = dither::take_settings(&self.egui.ctx);
+        let regions: Vec<(Rect, Dithering)> = regions
+            .into_iter()
+            .map(|(rect, dithering)| (rect * pixels_per_point, dithering))
+            .collect();
+        self.fb.present(dithering, &regions);
+    }
+
+    fn draw_mesh(&mut self, mesh: &Mesh, clip_rect: Rect, pixels_per_point: f32) {
+        if mesh.is_empty() {
+            return;
+        }
+        let Some(texture) = self.textures.get(mesh.texture_id) else {
+            warn!("Mesh uses texture {:?} which was never set", mesh.texture_id);
+            return;
+        };
+        self.fb.draw_mesh(mesh, clip_rect, pixels_per_point, texture);
+    }
+    /// Sleeps until a repaint is due, or `max` passes
+    pub fn wait(&self, max: Duration) {
+        let mut deadline = Instant::now() + max;
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return;
+            }
+            // A later request may be due sooner than the ones before it
+            match self.repaint.recv_timeout(deadline - now) {
+                Ok(delay) => deadline = deadline.min(Instant::now() + delay),
+                Err(_) => return,
+            }
+        }
+    }
+
+    /// Shows the sleep screen from this frame on, with the frontlight off
+    fn fall_asleep(&mut self) {
+        debug!("Going to sleep");
+        self.sleeping = true;
+        suspend::store_current(&self.egui.ctx, true);
+        self.fb.request_full_refresh();
+        if let Some(light) = frontlight::frontlight(&self.egui.ctx) {
+            // Left alone if its level is unknown, there would be nothing to turn it back on to
+            self.light_before_sleep = light.brightness();
+            if self.light_before_sleep.is_some() {
+                if let Err(err) = light.set_brightness(0) {
+                    warn!("Failed to turn the frontlight off: {}", err);
+                }
+            }
+        }
+        self.cover = self.suspend.cover.as_deref().and_then(|path| {
+            suspend::load_cover(path, [self.fb.width(), self.fb.height()])
+        });
+        self.enter_power_state = self.suspend.write_power_state;
+    }
+
+    /// Leaves the sleep screen. The framebuffer may have been set up again while the device
+    /// slept, so FBInk looks at it again and the app is redrawn from scratch
+    fn wake(&mut self) {
+        debug!("Waking up");
+        self.sleeping = false;
+        suspend::store_current(&self.egui.ctx, false);
+        self.cover = None;
+        self.reinit();
+        self.fb.request_full_refresh();
+        if let (Some(light), Some(brightness)) = (
+            frontlight::frontlight(&self.egui.ctx),
+            self.light_before_sleep.take(),
+        ) {
+            if let Err(err) = light.set_brightness(brightness) {
+                warn!("Failed to turn the frontlight back on: {}", err);
+            }
+        }
+        self.egui.ctx.request_repaint();
+    }
+
+    /// Lays the app out again if the framebuffer changed
+    fn reinit(&mut self) {
+        self.reinit_at = Instant::now();
+        if self.fb.reinit() {
+            self.egui.update_viewport(&self.fb);
+            self.egui.ctx.request_repaint();
+        }
+    }
+
+    /// Hands the touches read since the last frame to the keyboard or to egui
+    fn handle_touches(&mut self, pixels_per_point: f32) {
+        let touches = match &self.touches {
+            Some(queue) => std::mem::take(&mut *queue.lock().unwrap()),
+            None => return,
+        };
+        // The screen is off for the user, touching it does nothing
+        if self.sleeping {
+            return;
+        }
+        let points_per_mm = gesture::points_per_mm(self.egui.dpi, pixels_per_point);
+        for touch in touches {
+            let pos = (self.fb.touch.apply(touch.pos).to_vec2() / pixels_per_point).to_pos2();
+            if let Some(keyboard) = &mut self.keyboard {
+                if keyboard.is_touched_by(touch.id)
+                    || (touch.phase == TouchPhase::Start && keyboard.contains(pos))
+                {
+                    let events = keyboard.touch(touch.id, touch.phase, pos, &mut self.clipboard);
+                    self.events.extend(events);
+                    // Key presses have to show up right away
+                    self.fb.request_fast_refresh(keyboard.area() * pixels_per_point);
+                    continue;
+                }
+            }
+            let events = self.gestures.touch(touch.id, touch.phase, pos, points_per_mm);
+            self.events.extend(events);
+        }
+        self.events.extend(self.gestures.poll());
+        // Nothing else wakes the runner while a finger is held still
+        if let Some(delay) = self.gestures.next_deadline() {
+            self.egui.ctx.request_repaint_after(delay);
+        }
+        gesture::store(&self.egui.ctx, self.gestures.take_gestures());
+    }
+
+    /// Hands the stylus samples read since the last frame to egui, and to the app
+    fn handle_pen(&mut self, pixels_per_point: f32) {
+        let samples = match &self.pen {
+            Some(queue) => std::mem::take(&mut *queue.lock().unwrap()),
+            None => return,
+        };
+        if self.sleeping {
+            return;
+        }
+        let mut handed = Vec::with_capacity(samples.len());
+        for mut sample in samples {
+            sample.pos = (self.fb.touch.apply(sample.pos).to_vec2() / pixels_per_point).to_pos2();
+            self.events.extend(pen::pointer_events(&self.last_pen, &sample));
+            self.last_pen = sample;
+            handed.push(sample);
+        }
+        pen::store(&self.egui.ctx, handed);
+    }
+
+    /// Hands the strokes inked since the last frame to the app, which draws them from now on
+    fn handle_ink(&mut self, pixels_per_point: f32) {
+        let strokes = match &self.ink {
+            Some(ink) => ink.lock().unwrap().take_strokes(),
+            None => return,
+        };
+        let mut handed = Vec::with_capacity(strokes.len());
+        for mut stroke in strokes {
+            let mut area = Rect::NOTHING;
+            for sample in &mut stroke.samples {
+                let pos = self.fb.touch.framebuffer_to_logical(sample.pos);
+                area.extend_with(pos);
+                sample.pos = (pos.to_vec2() / pixels_per_point).to_pos2();
+            }
+            // The screen isn't what the last frame left anymore
+            self.fb.forget_area(area.expand(stroke.width / 2.0 + 1.0));
+            stroke.width /= pixels_per_point;
+            handed.push(stroke);
         }
+        ink::store_strokes(&self.egui.ctx, handed);
     }
+
+    /// Gives the ink thread the regions the app asked for this frame
+    fn update_ink(&mut self, pixels_per_point: f32) {
+        let Some(ink) = &self.ink else {
+            return;
+        };
+        let mut regions = ink::take_regions(&self.egui.ctx);
+        // Nothing is inked over the sleep screen
+        if self.sleeping {
+            regions.clear();
+        }
+        let regions = regions
+            .into_iter()
+            .filter_map(|(rect, width)| {
+                let area = self.fb.to_framebuffer_area(rect * pixels_per_point)?;
+                Some((area, width * pixels_per_point))
+            })
+            .collect();
+        ink.lock()
+            .unwrap()
+            .update(self.fb.cfg, self.fb.touch, self.fb.soft_inverted(), regions);
+    }
+
     pub fn next_frame(&mut self) {
         let timer = self.egui.get_start_time();
 
+        // Applied before the frame is laid out, so it's drawn once in the new orientation
+        if let Some(rotation) = rotation::take_request(&self.egui.ctx) {
+            if rotation != self.fb.rotation() {
+                self.fb.set_rotation(rotation);
+                self.egui.update_viewport(&self.fb);
+            }
+        }
+        if let Some(theme) = theme::take_request(&self.egui.ctx) {
+            self.egui.set_theme(theme);
+        }
+        if let Some(zoom_factor) = zoom::take_request(&self.egui.ctx) {
+            self.egui.set_zoom_factor(zoom_factor);
+            self.egui.update_viewport(&self.fb);
+        }
+        if let Some(profile) = touch_profile::take_request(&self.egui.ctx) {
+            self.egui.set_touch_profile(profile);
+        }
+        if let Some(mode) = night_mode::take_request(&self.egui.ctx) {
+            self.egui.set_night_mode(&mut self.fb, mode);
+        }
+        if let Some(sleep) = suspend::take_request(&self.egui.ctx) {
+            if sleep && !self.sleeping {
+                self.fall_asleep();
+            } else if !sleep && self.sleeping {
+                self.wake();
+            }
+        }
+        if self.reinit_at.elapsed() >= REINIT_INTERVAL {
+            self.reinit();
+        }
+        // The battery drains slowly, and the runner wakes up regularly anyway
+        if self
+            .power_read
+            .map_or(true, |read| read.elapsed() >= POWER_STATUS_INTERVAL)
+        {
+            device::store_power_status(&self.egui.ctx, &self.device);
+            self.power_read = Some(Instant::now());
+        }
+
+        // In points, so it follows the zoom
+        let pixels_per_point = self.egui.pixels_per_point();
+        let screen = Rect {
+            min: Pos2 {
+                x: 0.0,
+                y: 0.0,
+            },
+            max: Pos2 {
+                x: self.fb.width() as f32 / pixels_per_point,
+                y: self.fb.height() as f32 / pixels_per_point,
+            },
+        };
+        // The app is laid out next to the keyboard, so it can't hide the text being typed
+        let mut app_rect = screen;
+        if let Some(keyboard) = &mut self.keyboard {
+            let height = keyboard.height(self.egui.dpi, pixels_per_point);
+            app_rect = keyboard.set_area(screen, height);
+        }
+        self.handle_touches(pixels_per_point);
+        self.handle_pen(pixels_per_point);
+        self.handle_ink(pixels_per_point);
+        if clipboard::take_request(&self.egui.ctx) {
+            self.events.push(Event::Paste(self.clipboard.get()));
+        }
+
         let raw_input = RawInput {
-            screen_rect: Some(Rect {
-                min: Pos2 {
-                    x: 0.0,
-                    y: 0.0,
-                },
-                max: Pos2 {
-                    x: self.fb.state.screen_width as f32,
-                    y: self.fb.state.screen_height as f32,
-                },
-            }),
+            screen_rect: Some(app_rect),
             time: timer.map(|v| v as f64),
             predicted_dt: 1.0/60.0,
             modifiers: Default::default(),
-            events: Vec::new(),
+            events: std::mem::take(&mut self.events),
             max_texture_side: Some(2048), // Increase this if warnings of texture sizes appear?
             hovered_files: Vec::new(),
             dropped_files: Vec::new(),
@@ -119,28 +518,101 @@ impl AppRunner {
 
         self.egui.ctx.begin_frame(raw_input);
 
-        // in /home/szybet/.cargo/registry/src/index.crates.io-6f17d22bba15001f/egui-0.27.2/src/context.rs
-        // ContextImpl::begin_frame_mut
-        // if let Some(new_zoom_factor) = self.new_zoom_factor.take()
-        // to
-        // if let Some(new_zoom_factor) = self.new_zoom_factor
-        /*
-        // Doesn't really work
-        if timer.is_none() {
-            self.egui.ctx.set_zoom_factor(self.egui.zoom_factor);
-            debug!("Setting initial zoom factor");
+        self.egui.app.update(&self.egui.ctx, &mut frame);
+        if let Some(keyboard) = &self.keyboard {
+            if !self.sleeping {
+                keyboard.paint(&self.egui.ctx);
+            }
         }
 
-        self.egui.ctx.request_repaint();
-        */
+        let output = self.egui.ctx.end_frame();
 
-        self.egui.app.update(&self.egui.ctx, &mut frame);
+        // The app may zoom through egui itself, the next screen rect has to follow
+        let zoom_factor = self.egui.ctx.zoom_factor();
+        if self.egui.native_zoom && zoom_factor != self.egui.zoom_factor {
+            self.egui.set_zoom_factor(zoom_factor);
+            self.egui.update_viewport(&self.fb);
+        }
+        image_loader::forget_evicted(&self.egui.ctx);
+        self.update_ink(pixels_per_point);
 
-        let output = self.egui.ctx.end_frame();
+        let screenshot_requested = output
+            .viewport_output
+            .get(&self.egui.view_port_id)
+            .is_some_and(|viewport| {
+                viewport
+                    .commands
+                    .iter()
+                    .any(|command| matches!(command, ViewportCommand::Screenshot))
+            });
+
+        let platform_output = output.platform_output;
+        if !platform_output.copied_text.is_empty() {
+            self.clipboard.set(platform_output.copied_text);
+        }
+        if let Some(open_url) = &platform_output.open_url {
+            platform::open_url(&self.egui.ctx, open_url, self.browser.as_deref());
+        }
+        platform::store_events(&self.egui.ctx, platform_output.events);
+        // cursor_icon is left alone, a touch panel has no pointer to draw
+
+        // Text edits ask for an IME while they have the focus
+        if let Some(keyboard) = &mut self.keyboard {
+            if keyboard.set_visible(platform_output.ime.is_some()) {
+                // The app gets a different screen rect
+                self.egui.ctx.request_repaint();
+            }
+            keyboard.set_field(platform_output.ime.map(|ime| ime.rect));
+        }
+
+        // Textures have to be ready before drawing, and freed only once nothing uses them anymore
+        for (id, image_delta) in output.textures_delta.set {
+            self.textures.set(id, image_delta);
+        }
 
-        // There is nothing interesting
-        // let viewport_out = output.viewport_output.get(&self.egui.view_port_id).unwrap();
+        let cover = self
+            .cover
+            .as_ref()
+            .filter(|cover| cover.len() == self.fb.shadow.len());
+        if let Some(cover) = cover {
+            self.fb.shadow.copy_from_slice(cover);
+            let (dithering, _) = dither::take_settings(&self.egui.ctx);
+            self.fb.present(dithering, &[]);
+        } else {
+            self.draw_shapes(output.shapes);
+        }
+
+        // Only once the sleep screen is fully shown, it stays up while the device sleeps.
+        // Waking up is left to the power button, which is what wakes the device most of the time
+        if std::mem::take(&mut self.enter_power_state) {
+            self.fb.wait_for_complete();
+            if let Err(err) = suspend::enter_power_state(self.device.sysfs_root()) {
+                error!("Failed to suspend: {}", err);
+            }
+        }
 
-        self.draw_shapes(output.shapes);
+        // Taken once the frame is on the screen, so it's what the user sees
+        for request in dump::take_requests(&self.egui.ctx) {
+            let result = if request.shadow {
+                self.fb.dump_shadow(&request.path)
+            } else {
+                self.fb.dump(&request.path)
+            };
+            if let Err(err) = result {
+                error!("Failed to dump the screen to {}: {}", request.path.display(), err);
+            }
+        }
+        if screenshot_requested {
+            debug!("Taking a screenshot");
+            self.events.push(Event::Screenshot {
+                viewport_id: self.egui.view_port_id,
+                image: Arc::new(self.fb.screenshot()),
+            });
+            self.egui.ctx.request_repaint();
+        }
+
+        for id in output.textures_delta.free {
+            self.textures.free(id);
+        }
     }
 }
@@ -0,0 +1,63 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use egui::{Context, Id};
+use log::{debug, warn};
+
+/// Text copied by the app. With a file, it's shared with the other apps using the same one:
+/// the last copy wins, whichever app it came from
+pub(crate) struct Clipboard {
+    text: String,
+    file: Option<PathBuf>,
+}
+
+impl Clipboard {
+    pub fn new(file: Option<PathBuf>) -> Self {
+        Self {
+            text: String::new(),
+            file,
+        }
+    }
+
+    pub fn set(&mut self, text: String) {
+        if let Some(file) = &self.file {
+            // Written aside and renamed, so another app never reads half of it
+            let tmp = file.with_extension(format!("{}.tmp", std::process::id()));
+            let written = fs::write(&tmp, &text).and_then(|_| fs::rename(&tmp, file));
+            if let Err(err) = written {
+                warn!("Failed to write the clipboard to {}: {}", file.display(), err);
+                let _ = fs::remove_file(&tmp);
+            }
+        }
+        debug!("Copied {} bytes", text.len());
+        self.text = text;
+    }
+
+    pub fn get(&mut self) -> String {
+        if let Some(file) = &self.file {
+            match fs::read_to_string(file) {
+                Ok(text) => self.text = text,
+                // Nothing was copied yet
+                Err(err) if err.kind() == ErrorKind::NotFound => {}
+                Err(err) => warn!("Failed to read the clipboard from {}: {}", file.display(), err),
+            }
+        }
+        self.text.clone()
+    }
+}
+
+fn requested_id() -> Id {
+    Id::new("egui_fbink_requested_paste")
+}
+
+/// Pastes the clipboard into the focused text edit, with the next frame. Copying goes through
+/// egui, with `Context::copy_text`
+pub fn paste(ctx: &Context) {
+    ctx.data_mut(|data| data.insert_temp(requested_id(), true));
+}
+
+pub(crate) fn take_request(ctx: &Context) -> bool {
+    ctx.data_mut(|data| data.remove_temp::<bool>(requested_id()))
+        .unwrap_or(false)
+}
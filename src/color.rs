@@ -0,0 +1,187 @@
+use egui::Color32;
+
+/// How a single pixel is laid out in the framebuffer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// 4-bit gray, two pixels per byte
+    Gray4,
+    Gray8,
+    Rgb565,
+    /// Stored as B, G, R in memory
+    Bgr24,
+    /// Stored as B, G, R, A in memory
+    Bgra32,
+}
+
+impl PixelFormat {
+    pub fn from_bpp(bpp: u32) -> Option<Self> {
+        match bpp {
+            4 => Some(Self::Gray4),
+            8 => Some(Self::Gray8),
+            16 => Some(Self::Rgb565),
+            24 => Some(Self::Bgr24),
+            32 => Some(Self::Bgra32),
+            _ => None,
+        }
+    }
+
+    pub fn bits_per_pixel(self) -> u32 {
+        match self {
+            Self::Gray4 => 4,
+            Self::Gray8 => 8,
+            Self::Rgb565 => 16,
+            Self::Bgr24 => 24,
+            Self::Bgra32 => 32,
+        }
+    }
+
+    /// Converts a color to the bytes of a pixel in this format, in memory order. Only the
+    /// first `bits_per_pixel / 8` are used, `Gray4` is the low nibble of the first one.
+    /// Alpha is only kept by `Bgra32`, blend before packing
+    pub fn pack(self, color: Color32) -> [u8; 4] {
+        let (r, g, b) = (color.r(), color.g(), color.b());
+        match self {
+            Self::Gray4 => [quantize_gray(luminance(color)) / 17, 0, 0, 0],
+            Self::Gray8 => [luminance(color), 0, 0, 0],
+            Self::Rgb565 => {
+                let raw = ((r as u16 >> 3) << 11) | ((g as u16 >> 2) << 5) | (b as u16 >> 3);
+                let [low, high] = raw.to_le_bytes();
+                [low, high, 0, 0]
+            }
+            Self::Bgr24 => [b, g, r, 0],
+            Self::Bgra32 => [b, g, r, color.a()],
+        }
+    }
+
+    /// The reverse of `pack`
+    pub fn unpack(self, bytes: &[u8]) -> Color32 {
+        match self {
+            Self::Gray4 => Color32::from_gray((bytes[0] & 0xF) * 17),
+            Self::Gray8 => Color32::from_gray(bytes[0]),
+            Self::Rgb565 => {
+                let raw = u16::from_le_bytes([bytes[0], bytes[1]]);
+                // Repeat the high bits into the low ones so full intensity stays 255
+                let r = (raw >> 11) as u8;
+                let g = ((raw >> 5) & 0x3F) as u8;
+                let b = (raw & 0x1F) as u8;
+                Color32::from_rgb(
+                    (r << 3) | (r >> 2),
+                    (g << 2) | (g >> 4),
+                    (b << 3) | (b >> 2),
+                )
+            }
+            Self::Bgr24 => Color32::from_rgb(bytes[2], bytes[1], bytes[0]),
+            Self::Bgra32 => {
+                Color32::from_rgba_premultiplied(bytes[2], bytes[1], bytes[0], bytes[3])
+            }
+        }
+    }
+}
+
+pub fn luminance(color: Color32) -> u8 {
+    rgb_to_gray(color.r(), color.g(), color.b())
+}
+
+pub fn rgb_to_gray(r: u8, g: u8, b: u8) -> u8 {
+    // 709 formula
+    (0.2126 * (r as f32) + 0.7152 * (g as f32) + 0.0722 * (b as f32)).round() as u8
+}
+
+/// Rounds a gray value to the nearest of the 16 levels e-ink panels can show
+pub fn quantize_gray(gray: u8) -> u8 {
+    ((gray as u32 * 15 + 127) / 255 * 17) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Color32 = Color32::from_rgb(255, 0, 0);
+    const GREEN: Color32 = Color32::from_rgb(0, 255, 0);
+    const BLUE: Color32 = Color32::from_rgb(0, 0, 255);
+
+    #[test]
+    fn luminance_weights_each_channel() {
+        assert_eq!(luminance(RED), 54);
+        assert_eq!(luminance(GREEN), 182);
+        assert_eq!(luminance(BLUE), 18);
+        assert_eq!(luminance(Color32::WHITE), 255);
+        assert_eq!(luminance(Color32::BLACK), 0);
+    }
+
+    #[test]
+    fn quantize_gray_snaps_to_16_levels() {
+        assert_eq!(quantize_gray(0), 0);
+        assert_eq!(quantize_gray(8), 0);
+        assert_eq!(quantize_gray(9), 17);
+        assert_eq!(quantize_gray(128), 136);
+        assert_eq!(quantize_gray(255), 255);
+        for gray in 0..=255 {
+            assert_eq!(quantize_gray(gray) % 17, 0);
+        }
+    }
+
+    #[test]
+    fn from_bpp() {
+        assert_eq!(PixelFormat::from_bpp(4), Some(PixelFormat::Gray4));
+        assert_eq!(PixelFormat::from_bpp(8), Some(PixelFormat::Gray8));
+        assert_eq!(PixelFormat::from_bpp(16), Some(PixelFormat::Rgb565));
+        assert_eq!(PixelFormat::from_bpp(24), Some(PixelFormat::Bgr24));
+        assert_eq!(PixelFormat::from_bpp(32), Some(PixelFormat::Bgra32));
+        assert_eq!(PixelFormat::from_bpp(2), None);
+    }
+
+    #[test]
+    fn gray4() {
+        let format = PixelFormat::Gray4;
+        assert_eq!(format.pack(Color32::BLACK)[0], 0x0);
+        assert_eq!(format.pack(Color32::WHITE)[0], 0xF);
+        assert_eq!(format.pack(RED)[0], 0x3);
+        assert_eq!(format.pack(GREEN)[0], 0xB);
+        assert_eq!(format.pack(BLUE)[0], 0x1);
+        assert_eq!(format.unpack(&[0xF]), Color32::WHITE);
+        assert_eq!(format.unpack(&[0xB]), Color32::from_gray(187));
+    }
+
+    #[test]
+    fn gray8() {
+        let format = PixelFormat::Gray8;
+        assert_eq!(format.pack(Color32::BLACK)[0], 0);
+        assert_eq!(format.pack(Color32::WHITE)[0], 255);
+        assert_eq!(format.pack(RED)[0], 54);
+        assert_eq!(format.pack(GREEN)[0], 182);
+        assert_eq!(format.pack(BLUE)[0], 18);
+        assert_eq!(format.unpack(&[182]), Color32::from_gray(182));
+    }
+
+    #[test]
+    fn rgb565() {
+        let format = PixelFormat::Rgb565;
+        assert_eq!(format.pack(RED)[..2], [0x00, 0xF8]);
+        assert_eq!(format.pack(GREEN)[..2], [0xE0, 0x07]);
+        assert_eq!(format.pack(BLUE)[..2], [0x1F, 0x00]);
+        for color in [RED, GREEN, BLUE, Color32::WHITE, Color32::BLACK] {
+            assert_eq!(format.unpack(&format.pack(color)), color);
+        }
+    }
+
+    #[test]
+    fn bgr24() {
+        let format = PixelFormat::Bgr24;
+        assert_eq!(format.pack(RED)[..3], [0, 0, 255]);
+        assert_eq!(format.pack(GREEN)[..3], [0, 255, 0]);
+        assert_eq!(format.pack(BLUE)[..3], [255, 0, 0]);
+        let color = Color32::from_rgb(12, 34, 56);
+        assert_eq!(format.unpack(&format.pack(color)), color);
+    }
+
+    #[test]
+    fn bgra32() {
+        let format = PixelFormat::Bgra32;
+        assert_eq!(format.pack(RED), [0, 0, 255, 255]);
+        assert_eq!(format.pack(GREEN), [0, 255, 0, 255]);
+        assert_eq!(format.pack(BLUE), [255, 0, 0, 255]);
+        let color = Color32::from_rgba_premultiplied(12, 34, 56, 78);
+        assert_eq!(format.unpack(&format.pack(color)), color);
+    }
+}
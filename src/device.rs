@@ -0,0 +1,245 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use egui::{Context, Id};
+use log::warn;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChargeState {
+    Charging,
+    Discharging,
+    /// Plugged in, but not charging
+    NotCharging,
+    Full,
+    #[default]
+    Unknown,
+}
+
+impl ChargeState {
+    fn parse(status: &str) -> Self {
+        match status.trim() {
+            "Charging" => ChargeState::Charging,
+            "Discharging" => ChargeState::Discharging,
+            "Not charging" => ChargeState::NotCharging,
+            "Full" => ChargeState::Full,
+            _ => ChargeState::Unknown,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Battery {
+    /// Name of the supply in sysfs, like `mc13892_bat`
+    pub name: String,
+    /// Percent
+    pub capacity: u8,
+    pub state: ChargeState,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PowerStatus {
+    /// The first battery found, `None` on devices without one
+    pub battery: Option<Battery>,
+    /// A charger or USB cable is plugged in
+    pub plugged_in: bool,
+}
+
+/// Reads the device's state from sysfs
+#[derive(Clone, Debug)]
+pub struct Device {
+    sysfs_root: PathBuf,
+}
+
+impl Default for Device {
+    fn default() -> Self {
+        Self::new("/sys")
+    }
+}
+
+impl Device {
+    /// `sysfs_root` is normally `/sys`, another directory laid out the same way can fake it
+    pub fn new(sysfs_root: impl Into<PathBuf>) -> Self {
+        Self {
+            sysfs_root: sysfs_root.into(),
+        }
+    }
+
+    pub fn sysfs_root(&self) -> &Path {
+        &self.sysfs_root
+    }
+
+    /// Goes through `class/power_supply`, every call reads it again
+    pub fn power_status(&self) -> io::Result<PowerStatus> {
+        let mut supplies: Vec<_> = fs::read_dir(self.sysfs_root.join("class/power_supply"))?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .collect();
+        supplies.sort();
+
+        let mut status = PowerStatus::default();
+        for supply in supplies {
+            let read = |name: &str| fs::read_to_string(supply.join(name)).ok();
+            let kind = read("type").unwrap_or_default();
+            match kind.trim() {
+                "Battery" => {
+                    if status.battery.is_some() {
+                        continue;
+                    }
+                    let Some(capacity) = read("capacity").and_then(|c| c.trim().parse::<u8>().ok())
+                    else {
+                        continue;
+                    };
+                    let state =
+                        read("status").map_or(ChargeState::Unknown, |s| ChargeState::parse(&s));
+                    // Some drivers only tell through the battery that a charger is there
+                    if matches!(state, ChargeState::Charging | ChargeState::Full) {
+                        status.plugged_in = true;
+                    }
+                    status.battery = Some(Battery {
+                        name: supply
+                            .file_name()
+                            .map(|name| name.to_string_lossy().into_owned())
+                            .unwrap_or_default(),
+                        capacity: capacity.min(100),
+                        state,
+                    });
+                }
+                // Mains, USB and its variants
+                _ => {
+                    if read("online").is_some_and(|online| online.trim() == "1") {
+                        status.plugged_in = true;
+                    }
+                }
+            }
+        }
+        Ok(status)
+    }
+}
+
+fn power_id() -> Id {
+    Id::new("egui_fbink_power_status")
+}
+
+/// The power status as the runner last read it, every few seconds
+pub fn power_status(ctx: &Context) -> PowerStatus {
+    ctx.data(|data| data.get_temp(power_id()).unwrap_or_default())
+}
+
+pub(crate) fn store_power_status(ctx: &Context, device: &Device) {
+    match device.power_status() {
+        Ok(status) => ctx.data_mut(|data| data.insert_temp(power_id(), status)),
+        Err(err) => warn!(
+            "Failed to read the power status from {}: {}",
+            device.sysfs_root().display(),
+            err
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fake sysfs tree, removed when dropped
+    struct FakeSysfs(PathBuf);
+
+    impl FakeSysfs {
+        fn new(name: &str) -> Self {
+            let root =
+                std::env::temp_dir().join(format!("egui-fbink-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(root.join("class/power_supply")).unwrap();
+            Self(root)
+        }
+
+        fn supply(&self, name: &str, files: &[(&str, &str)]) {
+            let dir = self.0.join("class/power_supply").join(name);
+            fs::create_dir_all(&dir).unwrap();
+            for (file, content) in files {
+                fs::write(dir.join(file), format!("{}\n", content)).unwrap();
+            }
+        }
+    }
+
+    impl Drop for FakeSysfs {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn reads_a_discharging_battery() {
+        let sysfs = FakeSysfs::new("discharging");
+        sysfs.supply(
+            "mc13892_bat",
+            &[
+                ("type", "Battery"),
+                ("capacity", "73"),
+                ("status", "Discharging"),
+            ],
+        );
+        sysfs.supply("mc13892_charger", &[("type", "Mains"), ("online", "0")]);
+
+        let status = Device::new(&sysfs.0).power_status().unwrap();
+        assert_eq!(
+            status,
+            PowerStatus {
+                battery: Some(Battery {
+                    name: "mc13892_bat".to_owned(),
+                    capacity: 73,
+                    state: ChargeState::Discharging,
+                }),
+                plugged_in: false,
+            }
+        );
+    }
+
+    #[test]
+    fn charging_means_plugged_in() {
+        let sysfs = FakeSysfs::new("charging");
+        sysfs.supply(
+            "battery",
+            &[
+                ("type", "Battery"),
+                ("capacity", "20"),
+                ("status", "Charging"),
+            ],
+        );
+
+        let status = Device::new(&sysfs.0).power_status().unwrap();
+        assert!(status.plugged_in);
+        assert_eq!(status.battery.unwrap().state, ChargeState::Charging);
+    }
+
+    #[test]
+    fn online_charger_means_plugged_in() {
+        let sysfs = FakeSysfs::new("usb");
+        sysfs.supply(
+            "bd71827_bat",
+            &[
+                ("type", "Battery"),
+                ("capacity", "100"),
+                ("status", "Not charging"),
+            ],
+        );
+        sysfs.supply("usb", &[("type", "USB"), ("online", "1")]);
+
+        let status = Device::new(&sysfs.0).power_status().unwrap();
+        assert!(status.plugged_in);
+        assert_eq!(status.battery.unwrap().state, ChargeState::NotCharging);
+    }
+
+    #[test]
+    fn no_battery() {
+        let sysfs = FakeSysfs::new("no-battery");
+        let status = Device::new(&sysfs.0).power_status().unwrap();
+        assert_eq!(status, PowerStatus::default());
+    }
+
+    #[test]
+    fn missing_sysfs_is_an_error() {
+        let device = Device::new("/nonexistent/egui-fbink-sysfs");
+        assert!(device.power_status().is_err());
+    }
+}
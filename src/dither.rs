@@ -0,0 +1,124 @@
+use egui::{Context, Id, Rect};
+
+use crate::color::quantize_gray;
+
+/// How the 256 grays egui draws with are reduced to the 16 the panel can show
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Dithering {
+    /// Round to the nearest level. Keeps text and flat widgets crisp
+    #[default]
+    None,
+    /// 4x4 ordered dithering, stable from frame to frame
+    Bayer,
+    /// Error diffusion, smoothest gradients
+    FloydSteinberg,
+    /// Error diffusion that drops some of the error, more contrast than Floyd-Steinberg
+    Atkinson,
+}
+
+const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+// (dx, dy, weight)
+const FLOYD_STEINBERG: (&[(isize, usize, i32)], i32) =
+    (&[(1, 0, 7), (-1, 1, 3), (0, 1, 5), (1, 1, 1)], 16);
+const ATKINSON: (&[(isize, usize, i32)], i32) = (
+    &[(1, 0, 1), (2, 0, 1), (-1, 1, 1), (0, 1, 1), (1, 1, 1), (0, 2, 1)],
+    8,
+);
+
+fn dithering_id() -> Id {
+    Id::new("egui_fbink_dithering")
+}
+
+fn regions_id() -> Id {
+    Id::new("egui_fbink_dither_regions")
+}
+
+/// Sets the dithering used for the whole screen
+pub fn set_dithering(ctx: &Context, dithering: Dithering) {
+    ctx.data_mut(|data| data.insert_temp(dithering_id(), dithering));
+}
+
+/// Uses another dithering inside `rect` for this frame only, for example for an image.
+/// Call it every frame the region is shown
+pub fn dither_region(ctx: &Context, rect: Rect, dithering: Dithering) {
+    ctx.data_mut(|data| {
+        data.get_temp_mut_or_default::<Vec<(Rect, Dithering)>>(regions_id())
+            .push((rect, dithering))
+    });
+}
+
+/// The global dithering, and the regions requested during the last frame (in points)
+pub(crate) fn take_settings(ctx: &Context) -> (Dithering, Vec<(Rect, Dithering)>) {
+    ctx.data_mut(|data| {
+        (
+            data.get_temp(dithering_id()).unwrap_or_default(),
+            data.remove_temp(regions_id()).unwrap_or_default(),
+        )
+    })
+}
+
+/// Quantizes `src` into `dst` inside the pixel area `[left, top, right, bottom)`
+pub fn quantize(
+    src: &[u8],
+    dst: &mut [u8],
+    width: usize,
+    [left, top, right, bottom]: [usize; 4],
+    dithering: Dithering,
+) {
+    match dithering {
+        Dithering::None => {
+            for y in top..bottom {
+                for x in left..right {
+                    dst[y * width + x] = quantize_gray(src[y * width + x]);
+                }
+            }
+        }
+        Dithering::Bayer => {
+            for y in top..bottom {
+                for x in left..right {
+                    // Spread the threshold over one step between two levels (17)
+                    let offset = (BAYER_4X4[y % 4][x % 4] as i32 * 2 - 15) * 17 / 32;
+                    let gray = (src[y * width + x] as i32 + offset).clamp(0, 255);
+                    dst[y * width + x] = quantize_gray(gray as u8);
+                }
+            }
+        }
+        Dithering::FloydSteinberg => diffuse(src, dst, width, [left, top, right, bottom], FLOYD_STEINBERG),
+        Dithering::Atkinson => diffuse(src, dst, width, [left, top, right, bottom], ATKINSON),
+    }
+}
+
+fn diffuse(
+    src: &[u8],
+    dst: &mut [u8],
+    width: usize,
+    [left, top, right, bottom]: [usize; 4],
+    (kernel, divisor): (&[(isize, usize, i32)], i32),
+) {
+    let area_width = right - left;
+    let mut values: Vec<i32> = Vec::with_capacity(area_width * (bottom - top));
+    for y in top..bottom {
+        values.extend(src[y * width + left..y * width + right].iter().map(|v| *v as i32));
+    }
+
+    for y in 0..bottom - top {
+        for x in 0..area_width {
+            let value = values[y * area_width + x].clamp(0, 255);
+            let level = quantize_gray(value as u8);
+            dst[(top + y) * width + left + x] = level;
+
+            let error = value - level as i32;
+            if error == 0 {
+                continue;
+            }
+            for (dx, dy, weight) in kernel {
+                let (nx, ny) = (x as isize + dx, y + dy);
+                if nx < 0 || nx as usize >= area_width || ny >= bottom - top {
+                    continue;
+                }
+                values[ny * area_width + nx as usize] += error * weight / divisor;
+            }
+        }
+    }
+}
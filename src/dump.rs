@@ -0,0 +1,38 @@
+use std::path::PathBuf;
+
+use egui::{Context, Id};
+
+/// A dump asked for by the app, handled once the next frame is on the screen
+#[derive(Clone, Debug)]
+pub(crate) struct DumpRequest {
+    pub path: PathBuf,
+    /// The frame as drawn, before it's quantized, instead of what the panel shows
+    pub shadow: bool,
+}
+
+fn requests_id() -> Id {
+    Id::new("egui_fbink_dump_requests")
+}
+
+/// Saves what is on the screen to `path` once the next frame is shown, as PGM if it ends in
+/// `.pgm` and PNG otherwise. Failures are only logged
+pub fn dump(ctx: &Context, path: impl Into<PathBuf>) {
+    request(ctx, path.into(), false);
+}
+
+/// Same as `dump`, but for the frame as it was drawn, before it's quantized
+pub fn dump_shadow(ctx: &Context, path: impl Into<PathBuf>) {
+    request(ctx, path.into(), true);
+}
+
+fn request(ctx: &Context, path: PathBuf, shadow: bool) {
+    ctx.data_mut(|data| {
+        data.get_temp_mut_or_default::<Vec<DumpRequest>>(requests_id())
+            .push(DumpRequest { path, shadow })
+    });
+    ctx.request_repaint();
+}
+
+pub(crate) fn take_requests(ctx: &Context) -> Vec<DumpRequest> {
+    ctx.data_mut(|data| data.remove_temp(requests_id()).unwrap_or_default())
+}
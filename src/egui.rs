@@ -6,10 +6,17 @@ use egui::FontFamily::Proportional;
 use egui::FontId;
 use egui::Rounding;
 use egui::TextStyle::*;
-use egui::{Context, Pos2, Rect, Vec2, ViewportId, ViewportInfo};
+use egui::epaint::Shadow;
+use egui::{Context, Pos2, Rect, Style, Vec2, ViewportId, ViewportInfo};
+use log::warn;
 
-use crate::eink_theme::style;
+use crate::eink_theme::invert_visuals;
 use crate::fbink::FBInkBackend;
+use crate::night_mode::{self, NightMode};
+use crate::theme::{self, Theme};
+use crate::touch_profile::TouchProfile;
+use crate::rotation;
+use crate::zoom;
 
 pub struct EguiStuff {
     pub ctx: Context,
@@ -23,6 +30,13 @@ pub struct EguiStuff {
         BuildHasherDefault<nohash_hasher::NoHashHasher<ViewportId>>,
     >,
     pub start_time: Option<SystemTime>,
+    /// The theme's style before zoom, touch sizing or night mode
+    pub base_style: Style,
+    pub touch_profile: Option<TouchProfile>,
+    /// Of the panel, to turn millimeters into points
+    pub dpi: f32,
+    /// Zoom through egui's own zoom factor instead of scaling the style
+    pub native_zoom: bool,
 }
 impl EguiStuff {
     pub fn new(
@@ -30,12 +44,19 @@ impl EguiStuff {
         fb: &FBInkBackend,
         pixel_per_point: f32,
         zoom_factor: f32,
+        native_zoom: bool,
+        touch_profile: Option<TouchProfile>,
     ) -> Self {
         let ctx = Context::default();
         ctx.set_embed_viewports(true);
-        ctx.set_pixels_per_point(pixel_per_point);
+        // `pixel_per_point` is given to egui as the native one of the viewport. The zoom is
+        // written straight into the options: `set_zoom_factor` would only apply it during
+        // the first frame, rescaling a screen rect egui doesn't know yet
+        if native_zoom {
+            ctx.options_mut(|options| options.zoom_factor = zoom_factor);
+        }
         ctx.set_visuals(egui::Visuals::light());
-        ctx.set_style(style()); // Set the eink style
+        ctx.set_style(Theme::default().style()); // Set the eink style
 
         /*
         let mut fonts = egui::FontDefinitions::default();
@@ -61,39 +82,99 @@ impl EguiStuff {
             BuildHasherDefault<nohash_hasher::NoHashHasher<ViewportId>>,
         > = Default::default();
         let view_port_id = ViewportId::default();
-        let mut view_port_info = ViewportInfo::default();
+        view_port_list.insert(view_port_id, ViewportInfo::default());
 
+        let mut egui_stuff = Self {
+            ctx,
+            app,
+            pixel_per_point,
+            zoom_factor,
+            view_port_id,
+            view_port_list,
+            start_time: None,
+            base_style: Theme::default().style(),
+            touch_profile,
+            dpi: fb.state.screen_dpi as f32,
+            native_zoom,
+        };
+        egui_stuff.update_viewport(fb);
+        egui_stuff
+    }
+
+    /// Describes the screen to egui again, after it was rotated for example
+    pub fn update_viewport(&mut self, fb: &FBInkBackend) {
         let screen_size = Some(Vec2 {
-            x: fb.state.screen_width as f32,
-            y: fb.state.screen_height as f32,
+            x: fb.width() as f32,
+            y: fb.height() as f32,
         });
 
         let screen_size_rect = Some(Rect {
             min: Pos2 { x: 0.0, y: 0.0 },
             max: Pos2 {
-                x: fb.state.screen_width as f32,
-                y: fb.state.screen_height as f32,
+                x: fb.width() as f32,
+                y: fb.height() as f32,
             },
         });
 
-        view_port_info.native_pixels_per_point = Some(pixel_per_point);
+        // egui wants these in points
+        let screen_size = screen_size.map(|size| size / self.pixels_per_point());
+        let screen_size_rect = screen_size_rect.map(|rect| rect / self.pixels_per_point());
+
+        let view_port_info = self.view_port_list.entry(self.view_port_id).or_default();
+        view_port_info.native_pixels_per_point = Some(self.pixel_per_point);
         view_port_info.monitor_size = screen_size;
         view_port_info.inner_rect = screen_size_rect;
         view_port_info.outer_rect = screen_size_rect;
         view_port_info.fullscreen = Some(true);
         view_port_info.focused = Some(true);
 
-        view_port_list.insert(view_port_id, view_port_info);
+        rotation::store_current(&self.ctx, fb.rotation());
+    }
 
-        Self {
-            ctx,
-            app,
-            pixel_per_point,
-            zoom_factor,
-            view_port_id,
-            view_port_list,
-            start_time: None,
+    /// Switches the theme and the panel for `mode`, what it was before is undone first
+    pub fn set_night_mode(&mut self, fb: &mut FBInkBackend, mode: NightMode) {
+        let current = night_mode::night_mode(&self.ctx);
+        if current == mode {
+            return;
+        }
+        night_mode::store_current(&self.ctx, mode);
+        let dark_theme = |mode| mode == NightMode::DarkTheme;
+        if dark_theme(current) != dark_theme(mode) {
+            self.apply_style();
+        }
+        fb.set_inverted(mode == NightMode::Inverted);
+    }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        theme::store_current(&self.ctx, theme);
+        self.base_style = theme.style();
+        self.apply_style();
+    }
+
+    pub fn set_touch_profile(&mut self, touch_profile: Option<TouchProfile>) {
+        self.touch_profile = touch_profile;
+        self.apply_style();
+    }
+
+    /// Builds the style from the base one: zoomed, sized for touch, and darkened in
+    /// night mode. Anything changing one of those goes through here
+    pub fn apply_style(&mut self) {
+        let mut style = self.base_style.clone();
+        if !self.native_zoom {
+            zoom_style(&mut style, self.zoom_factor);
+        }
+        zoom::store_current(&self.ctx, self.zoom_factor);
+        if let Some(touch_profile) = self.touch_profile {
+            if self.dpi > 0.0 {
+                touch_profile.apply(&mut style, self.dpi, self.pixels_per_point());
+            } else {
+                warn!("The screen DPI is unknown, widgets aren't sized for touch");
+            }
         }
+        if night_mode::night_mode(&self.ctx) == NightMode::DarkTheme {
+            invert_visuals(&mut style.visuals);
+        }
+        self.ctx.set_style(style);
     }
 
     // I don't thing this is needed at all
@@ -111,73 +192,181 @@ impl EguiStuff {
         }
     }
 
-    pub fn manage_zoom(&mut self) {
-        let mut style = (*self.ctx.style()).clone();
+    pub fn set_zoom_factor(&mut self, zoom_factor: f32) {
+        self.zoom_factor = zoom_factor;
+        if self.native_zoom {
+            self.ctx.options_mut(|options| options.zoom_factor = zoom_factor);
+        }
+        self.apply_style();
+    }
 
-        for ts in &mut style.text_styles {
-            ts.1.size *= self.zoom_factor;
+    /// What egui draws with, zoom included when egui does the zooming
+    pub fn pixels_per_point(&self) -> f32 {
+        if self.native_zoom {
+            self.pixel_per_point * self.zoom_factor
+        } else {
+            self.pixel_per_point
         }
+    }
+}
 
-        let rounding_zoom = |rounding: &mut Rounding| {
-            rounding.nw *= self.zoom_factor;
-            rounding.ne *= self.zoom_factor;
-            rounding.sw *= self.zoom_factor;
-            rounding.se *= self.zoom_factor;
-        };
+/// Scales everything in `style` that is a size by `zoom_factor`. Durations and colors
+/// are left alone. It has to start from an unscaled style, zooming twice compounds
+pub fn zoom_style(style: &mut Style, zoom_factor: f32) {
+    for ts in &mut style.text_styles {
+        ts.1.size *= zoom_factor;
+    }
 
-        let widget_visual_zoom = |widget_visuals: &mut WidgetVisuals| {
-            rounding_zoom(&mut widget_visuals.rounding);
-    
-            widget_visuals.bg_stroke.width *= self.zoom_factor;
-            widget_visuals.fg_stroke.width *= self.zoom_factor;
-    
-            widget_visuals.expansion *= self.zoom_factor;
-        };
+    let rounding_zoom = |rounding: &mut Rounding| {
+        rounding.nw *= zoom_factor;
+        rounding.ne *= zoom_factor;
+        rounding.sw *= zoom_factor;
+        rounding.se *= zoom_factor;
+    };
 
-        // Adjustments in the `Spacing` struct
-        style.spacing.item_spacing *= self.zoom_factor;
-        style.spacing.button_padding *= self.zoom_factor;
-        style.spacing.menu_margin.left *= self.zoom_factor;
-        style.spacing.menu_margin.right *= self.zoom_factor;
-        style.spacing.window_margin.top *= self.zoom_factor;
-        style.spacing.window_margin.bottom *= self.zoom_factor;
-        style.spacing.indent *= self.zoom_factor;
-        style.spacing.interact_size *= self.zoom_factor;
-        style.spacing.slider_width *= self.zoom_factor;
-        style.spacing.slider_rail_height *= self.zoom_factor;
-        style.spacing.combo_width *= self.zoom_factor;
-        style.spacing.text_edit_width *= self.zoom_factor;
-        style.spacing.icon_width *= self.zoom_factor;
-        style.spacing.icon_width_inner *= self.zoom_factor;
-        style.spacing.icon_spacing *= self.zoom_factor;
-        style.spacing.tooltip_width *= self.zoom_factor;
-        style.spacing.menu_width *= self.zoom_factor;
-        style.spacing.menu_spacing *= self.zoom_factor;
-        style.spacing.combo_height *= self.zoom_factor;
-
-        // Adjustments in the `Interaction` struct
-        style.interaction.interact_radius *= self.zoom_factor;
-        style.interaction.resize_grab_radius_side *= self.zoom_factor;
-        style.interaction.resize_grab_radius_corner *= self.zoom_factor;
-        style.interaction.tooltip_delay *= self.zoom_factor;
-
-        // Adjustments in the `Visuals` struct
-        rounding_zoom(&mut style.visuals.window_rounding);
-                
-        style.visuals.window_shadow.offset *= self.zoom_factor;
-
-        rounding_zoom(&mut style.visuals.menu_rounding);
-
-        style.visuals.resize_corner_size *= self.zoom_factor;
-        style.visuals.text_cursor.width *= self.zoom_factor;
-        style.visuals.clip_rect_margin *= self.zoom_factor;
-
-        widget_visual_zoom(&mut style.visuals.widgets.noninteractive);
-        widget_visual_zoom(&mut style.visuals.widgets.inactive);
-        widget_visual_zoom(&mut style.visuals.widgets.hovered);
-        widget_visual_zoom(&mut style.visuals.widgets.active);
-        widget_visual_zoom(&mut style.visuals.widgets.open);
+    let widget_visual_zoom = |widget_visuals: &mut WidgetVisuals| {
+        rounding_zoom(&mut widget_visuals.rounding);
 
-        self.ctx.set_style(style);
+        widget_visuals.bg_stroke.width *= zoom_factor;
+        widget_visuals.fg_stroke.width *= zoom_factor;
+
+        widget_visuals.expansion *= zoom_factor;
+    };
+
+    let shadow_zoom = |shadow: &mut Shadow| {
+        shadow.offset *= zoom_factor;
+        shadow.blur *= zoom_factor;
+        shadow.spread *= zoom_factor;
+    };
+
+    // Adjustments in the `Spacing` struct
+    style.spacing.item_spacing *= zoom_factor;
+    style.spacing.button_padding *= zoom_factor;
+    style.spacing.menu_margin *= zoom_factor;
+    style.spacing.window_margin *= zoom_factor;
+    style.spacing.indent *= zoom_factor;
+    style.spacing.interact_size *= zoom_factor;
+    style.spacing.slider_width *= zoom_factor;
+    style.spacing.slider_rail_height *= zoom_factor;
+    style.spacing.combo_width *= zoom_factor;
+    style.spacing.text_edit_width *= zoom_factor;
+    style.spacing.icon_width *= zoom_factor;
+    style.spacing.icon_width_inner *= zoom_factor;
+    style.spacing.icon_spacing *= zoom_factor;
+    style.spacing.tooltip_width *= zoom_factor;
+    style.spacing.menu_width *= zoom_factor;
+    style.spacing.menu_spacing *= zoom_factor;
+    style.spacing.combo_height *= zoom_factor;
+
+    // Adjustments in the `ScrollStyle` struct
+    style.spacing.scroll.bar_width *= zoom_factor;
+    style.spacing.scroll.handle_min_length *= zoom_factor;
+    style.spacing.scroll.bar_inner_margin *= zoom_factor;
+    style.spacing.scroll.bar_outer_margin *= zoom_factor;
+    style.spacing.scroll.floating_width *= zoom_factor;
+    style.spacing.scroll.floating_allocated_width *= zoom_factor;
+
+    // Adjustments in the `Interaction` struct
+    style.interaction.interact_radius *= zoom_factor;
+    style.interaction.resize_grab_radius_side *= zoom_factor;
+    style.interaction.resize_grab_radius_corner *= zoom_factor;
+
+    // Adjustments in the `Visuals` struct
+    rounding_zoom(&mut style.visuals.window_rounding);
+    rounding_zoom(&mut style.visuals.menu_rounding);
+
+    shadow_zoom(&mut style.visuals.window_shadow);
+    shadow_zoom(&mut style.visuals.popup_shadow);
+
+    style.visuals.window_stroke.width *= zoom_factor;
+    style.visuals.selection.stroke.width *= zoom_factor;
+    style.visuals.resize_corner_size *= zoom_factor;
+    style.visuals.text_cursor.width *= zoom_factor;
+    style.visuals.clip_rect_margin *= zoom_factor;
+
+    widget_visual_zoom(&mut style.visuals.widgets.noninteractive);
+    widget_visual_zoom(&mut style.visuals.widgets.inactive);
+    widget_visual_zoom(&mut style.visuals.widgets.hovered);
+    widget_visual_zoom(&mut style.visuals.widgets.active);
+    widget_visual_zoom(&mut style.visuals.widgets.open);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NoApp;
+
+    impl App for NoApp {
+        fn update(&mut self, _ctx: &Context, _frame: &mut eframe::Frame) {}
+    }
+
+    fn egui_stuff() -> EguiStuff {
+        EguiStuff {
+            ctx: Context::default(),
+            app: Box::new(NoApp),
+            pixel_per_point: 1.0,
+            zoom_factor: 1.0,
+            view_port_id: ViewportId::ROOT,
+            view_port_list: Default::default(),
+            start_time: None,
+            base_style: Theme::default().style(),
+            touch_profile: None,
+            dpi: 0.0,
+            native_zoom: false,
+        }
+    }
+
+    fn assert_zoomed(style: &Style, base: &Style, zoom_factor: f32) {
+        assert_eq!(
+            style.text_styles[&Body].size,
+            base.text_styles[&Body].size * zoom_factor
+        );
+        assert_eq!(
+            style.spacing.interact_size,
+            base.spacing.interact_size * zoom_factor
+        );
+        assert_eq!(
+            style.spacing.scroll.bar_width,
+            base.spacing.scroll.bar_width * zoom_factor
+        );
+        assert_eq!(
+            style.visuals.widgets.inactive.bg_stroke.width,
+            base.visuals.widgets.inactive.bg_stroke.width * zoom_factor
+        );
+        assert_eq!(
+            style.interaction.tooltip_delay,
+            base.interaction.tooltip_delay
+        );
+    }
+
+    #[test]
+    fn zooming_twice_does_not_compound() {
+        let mut egui = egui_stuff();
+        let base = egui.base_style.clone();
+        egui.set_zoom_factor(2.0);
+        egui.set_zoom_factor(2.0);
+        assert_zoomed(&egui.ctx.style(), &base, 2.0);
+    }
+
+    #[test]
+    fn zooming_back_restores_the_base_sizes() {
+        let mut egui = egui_stuff();
+        let base = egui.base_style.clone();
+        egui.set_zoom_factor(2.0);
+        egui.set_zoom_factor(1.0);
+        assert_zoomed(&egui.ctx.style(), &base, 1.0);
+    }
+
+    #[test]
+    fn zoom_style_leaves_durations_alone() {
+        let mut style = Style::default();
+        style.interaction.tooltip_delay = 0.5;
+        zoom_style(&mut style, 3.0);
+        assert_eq!(style.interaction.tooltip_delay, 0.5);
+        assert_eq!(
+            style.text_styles[&Body].size,
+            Style::default().text_styles[&Body].size * 3.0
+        );
     }
 }
@@ -68,8 +68,8 @@ pub fn style() -> Style {
             override_text_color: Some(Color32::from_rgb(0, 0, 0)),
             widgets: Widgets {
                 noninteractive: WidgetVisuals {
-                    bg_fill: Color32::from_rgba_premultiplied(248, 248, 248, 255),
-                    weak_bg_fill: Color32::from_rgba_premultiplied(248, 248, 248, 255),
+                    bg_fill: Color32::from_rgba_premultiplied(238, 238, 238, 255),
+                    weak_bg_fill: Color32::from_rgba_premultiplied(238, 238, 238, 255),
                     bg_stroke: Stroke {
                         width: 1.0,
                         color: Color32::from_rgba_premultiplied(0, 0, 0, 255),
@@ -82,12 +82,12 @@ pub fn style() -> Style {
                     },
                     fg_stroke: Stroke {
                         width: 1.0,
-                        color: Color32::from_rgba_premultiplied(80, 80, 80, 255),
+                        color: Color32::from_rgba_premultiplied(85, 85, 85, 255),
                     },
                     expansion: 0.0,
                 },
                 inactive: WidgetVisuals {
-                    bg_fill: Color32::from_rgba_premultiplied(177, 177, 177, 255),
+                    bg_fill: Color32::from_rgba_premultiplied(170, 170, 170, 255),
                     weak_bg_fill: Color32::from_rgba_premultiplied(255, 255, 255, 255),
                     bg_stroke: Stroke {
                         width: 1.5,
@@ -101,16 +101,16 @@ pub fn style() -> Style {
                     },
                     fg_stroke: Stroke {
                         width: 2.0,
-                        color: Color32::from_rgba_premultiplied(60, 60, 60, 255),
+                        color: Color32::from_rgba_premultiplied(68, 68, 68, 255),
                     },
                     expansion: 0.0,
                 },
                 hovered: WidgetVisuals {
-                    bg_fill: Color32::from_rgba_premultiplied(220, 220, 220, 255),
-                    weak_bg_fill: Color32::from_rgba_premultiplied(158, 158, 158, 255),
+                    bg_fill: Color32::from_rgba_premultiplied(221, 221, 221, 255),
+                    weak_bg_fill: Color32::from_rgba_premultiplied(153, 153, 153, 255),
                     bg_stroke: Stroke {
                         width: 1.0,
-                        color: Color32::from_rgba_premultiplied(105, 105, 105, 255),
+                        color: Color32::from_rgba_premultiplied(102, 102, 102, 255),
                     },
                     rounding: Rounding {
                         nw: 3.0,
@@ -125,8 +125,8 @@ pub fn style() -> Style {
                     expansion: 1.0,
                 },
                 active: WidgetVisuals {
-                    bg_fill: Color32::from_rgba_premultiplied(165, 165, 165, 255),
-                    weak_bg_fill: Color32::from_rgba_premultiplied(165, 165, 165, 255),
+                    bg_fill: Color32::from_rgba_premultiplied(170, 170, 170, 255),
+                    weak_bg_fill: Color32::from_rgba_premultiplied(170, 170, 170, 255),
                     bg_stroke: Stroke {
                         width: 1.0,
                         color: Color32::from_rgba_premultiplied(0, 0, 0, 255),
@@ -144,11 +144,11 @@ pub fn style() -> Style {
                     expansion: 1.0,
                 },
                 open: WidgetVisuals {
-                    bg_fill: Color32::from_rgba_premultiplied(220, 220, 220, 255),
-                    weak_bg_fill: Color32::from_rgba_premultiplied(220, 220, 220, 255),
+                    bg_fill: Color32::from_rgba_premultiplied(221, 221, 221, 255),
+                    weak_bg_fill: Color32::from_rgba_premultiplied(221, 221, 221, 255),
                     bg_stroke: Stroke {
                         width: 1.0,
-                        color: Color32::from_rgba_premultiplied(160, 160, 160, 255),
+                        color: Color32::from_rgba_premultiplied(153, 153, 153, 255),
                     },
                     rounding: Rounding {
                         nw: 2.0,
@@ -164,18 +164,19 @@ pub fn style() -> Style {
                 },
             },
             selection: Selection {
-                bg_fill: Color32::from_rgba_premultiplied(149, 149, 149, 255),
+                bg_fill: Color32::from_rgba_premultiplied(153, 153, 153, 255),
                 stroke: Stroke {
                     width: 1.0,
                     color: Color32::from_rgba_premultiplied(0, 0, 0, 255),
                 },
             },
-            hyperlink_color: Color32::from_rgba_premultiplied(109, 109, 109, 255),
-            faint_bg_color: Color32::from_rgba_premultiplied(5, 5, 5, 0),
+            hyperlink_color: Color32::from_rgba_premultiplied(102, 102, 102, 255),
+            faint_bg_color: Color32::from_rgba_premultiplied(0, 0, 0, 0),
             extreme_bg_color: Color32::from_rgba_premultiplied(255, 255, 255, 255),
-            code_bg_color: Color32::from_rgba_premultiplied(230, 230, 230, 255),
-            warn_fg_color: Color32::from_rgba_premultiplied(255, 100, 0, 255),
-            error_fg_color: Color32::from_rgba_premultiplied(255, 0, 0, 255),
+            code_bg_color: Color32::from_rgba_premultiplied(238, 238, 238, 255),
+            // Grays as dark as the orange and red egui-themer had, the panel has no colors
+            warn_fg_color: Color32::from_rgba_premultiplied(119, 119, 119, 255),
+            error_fg_color: Color32::from_rgba_premultiplied(51, 51, 51, 255),
             window_rounding: Rounding {
                 nw: 6.0,
                 ne: 6.0,
@@ -189,10 +190,10 @@ pub fn style() -> Style {
                 blur: Default::default(),
                 spread: Default::default(),
             },
-            window_fill: Color32::from_rgba_premultiplied(248, 248, 248, 255),
+            window_fill: Color32::from_rgba_premultiplied(238, 238, 238, 255),
             window_stroke: Stroke {
                 width: 1.0,
-                color: Color32::from_rgba_premultiplied(190, 190, 190, 255),
+                color: Color32::from_rgba_premultiplied(187, 187, 187, 255),
             },
             menu_rounding: Rounding {
                 nw: 6.0,
@@ -229,3 +230,43 @@ pub fn style() -> Style {
         ..Default::default()
     }
 }
+
+/// Turns the theme into its light on dark variant, or back. Only the colors change,
+/// so it can be done on top of zooming
+pub fn invert_visuals(visuals: &mut Visuals) {
+    visuals.dark_mode = !visuals.dark_mode;
+    visuals.override_text_color = visuals.override_text_color.map(invert_color);
+
+    for widget in [
+        &mut visuals.widgets.noninteractive,
+        &mut visuals.widgets.inactive,
+        &mut visuals.widgets.hovered,
+        &mut visuals.widgets.active,
+        &mut visuals.widgets.open,
+    ] {
+        widget.bg_fill = invert_color(widget.bg_fill);
+        widget.weak_bg_fill = invert_color(widget.weak_bg_fill);
+        widget.bg_stroke.color = invert_color(widget.bg_stroke.color);
+        widget.fg_stroke.color = invert_color(widget.fg_stroke.color);
+    }
+
+    visuals.selection.bg_fill = invert_color(visuals.selection.bg_fill);
+    visuals.selection.stroke.color = invert_color(visuals.selection.stroke.color);
+    visuals.hyperlink_color = invert_color(visuals.hyperlink_color);
+    visuals.faint_bg_color = invert_color(visuals.faint_bg_color);
+    visuals.extreme_bg_color = invert_color(visuals.extreme_bg_color);
+    visuals.code_bg_color = invert_color(visuals.code_bg_color);
+    visuals.warn_fg_color = invert_color(visuals.warn_fg_color);
+    visuals.error_fg_color = invert_color(visuals.error_fg_color);
+    visuals.window_shadow.color = invert_color(visuals.window_shadow.color);
+    visuals.window_fill = invert_color(visuals.window_fill);
+    visuals.window_stroke.color = invert_color(visuals.window_stroke.color);
+    visuals.panel_fill = invert_color(visuals.panel_fill);
+    visuals.popup_shadow.color = invert_color(visuals.popup_shadow.color);
+}
+
+/// Keeps the alpha, the theme's colors are premultiplied
+fn invert_color(color: Color32) -> Color32 {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    Color32::from_rgba_unmultiplied(255 - r, 255 - g, 255 - b, a)
+}
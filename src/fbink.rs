@@ -1,8 +1,8 @@
-use ::std::os::raw::c_int;
+use ::std::os::raw::{c_int, c_short};
 use core::convert::TryInto;
 use egui::{
-    epaint::{PathShape, RectShape, TextShape},
-    Shape,
+    epaint::Mesh,
+    Color32, ColorImage, Pos2, Rect, Shape, Vec2,
 };
 use embedded_graphics::{
     pixelcolor::{
@@ -10,35 +10,62 @@ use embedded_graphics::{
         Gray8, GrayColor, Rgb555, Rgb565, Rgb888,
     },
     prelude::*,
-    primitives::{
-        Circle, Line, Polyline, PrimitiveStyle, PrimitiveStyleBuilder, Rectangle, RoundedRectangle,
-    },
+    primitives::{Circle, Line, PrimitiveStyle, Rectangle},
 };
 use fbink_sys::fbink_get_state;
-use fbink_sys::fbink_print_ot;
 use fbink_sys::fbink_print_raw_data;
 use fbink_sys::fbink_put_pixel;
-use fbink_sys::fbink_put_pixel_rgba;
 use fbink_sys::fbink_refresh;
 use fbink_sys::fbink_refresh_rect;
-use fbink_sys::fbink_update_pen_colors;
-use fbink_sys::FBInkOTConfig;
-use fbink_sys::FBInkOTFit;
 use fbink_sys::FBInkState;
 use fbink_sys::BG_COLOR_INDEX_E_BG_WHITE;
 use fbink_sys::FG_COLOR_INDEX_E_FG_WHITE;
 use fbink_sys::{
-    fbink_add_ot_font, fbink_fill_rect_rgba, fbink_init, fbink_open, fbink_wait_for_complete,
-    FBInkConfig, FBInkRect, LAST_MARKER,
+    fbink_fill_rect_rgba, fbink_get_fb_pointer, fbink_init, fbink_open, fbink_reinit,
+    fbink_wait_for_complete, FBInkConfig, FBInkRect, LAST_MARKER, OK_BPP_CHANGE,
+    OK_GRAYSCALE_CHANGE, OK_LAYOUT_CHANGE, OK_ROTA_CHANGE, WFM_MODE_INDEX_E_WFM_DU,
+    WFM_MODE_INDEX_T,
 };
-use ffi::CString;
+use image::ImageFormat;
 use log::{debug, error, warn};
-use std::{ffi, fs, process::exit};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::process::exit;
+use std::sync::Mutex;
+
+use crate::color::{luminance, rgb_to_gray, PixelFormat};
+use crate::dither::{self, Dithering};
+use crate::raster;
+use crate::rotation::{Rotation, TouchTransform};
+use crate::textures::Texture;
+
+/// FBInk isn't thread safe, and the ink thread draws while the runner presents
+pub(crate) static FBINK_LOCK: Mutex<()> = Mutex::new(());
+
+/// Never a quantized level, so a pixel holding it always differs from the next frame
+const UNKNOWN_LEVEL: u8 = 1;
 
 pub struct FBInkBackend {
     pub cfg: FBInkConfig,
     pub fd: c_int,
     pub state: FBInkState,
+    /// The frame being drawn, in 8-bit gray. It's only pushed to the screen by `present`
+    pub shadow: Vec<u8>,
+    /// Quantized copy of what is on the screen right now
+    panel: Vec<u8>,
+    /// Scratch buffer for the quantized shadow buffer
+    levels: Vec<u8>,
+    /// Done in software, `state` keeps describing the framebuffer as it is
+    rotation: Rotation,
+    pub touch: TouchTransform,
+    /// Next `present` pushes and flashes the whole screen
+    full_refresh: bool,
+    /// Night mode on a panel that can't invert by itself
+    soft_invert: bool,
+    /// Next `present` uses a fast waveform in this area (in pixels), for quick feedback
+    /// that is soon redrawn
+    fast_refresh: Option<Rect>,
 }
 
 impl FBInkBackend {
@@ -62,33 +89,15 @@ impl FBInkBackend {
             fbink_get_state(&cfg, &mut state);
             // Why does it compile but it shows errors - sometimes
             debug!(
-                "Running on {:?}, codename: {:?}, platform: {:?}, with screen: {:?}x{:?}",
+                "Running on {:?}, codename: {:?}, platform: {:?}, with screen: {:?}x{:?} in {:?}",
                 x8_to_string(state.device_name),
                 x8_to_string(state.device_codename),
                 x8_to_string(state.device_platform),
                 state.screen_width,
-                state.screen_height
+                state.screen_height,
+                PixelFormat::from_bpp(state.bpp)
             );
 
-            static FONT_PATH: &str = "fonts/";
-            for entry in fs::read_dir(FONT_PATH).expect("fonts dir wasn't found") {
-                let real_entry = entry.unwrap();
-                let name = format!(
-                    "{}{}",
-                    FONT_PATH,
-                    real_entry.file_name().to_string_lossy().to_string()
-                );
-                if real_entry.file_type().unwrap().is_file() {
-                    debug!("Adding OT font: {}", name);
-                }
-                let c_text = CString::new(name.clone()).unwrap();
-                let c_chat = c_text.as_ptr();
-                // 0 as regular?
-                if fbink_add_ot_font(c_chat, 0) < 0 {
-                    error!("Failed to add font: {}", name);
-                }
-            }
-
             let mut cls_rect: FBInkRect = std::mem::zeroed();
             cls_rect.left = 0;
             cls_rect.top = 0;
@@ -98,144 +107,428 @@ impl FBInkBackend {
             fbink_wait_for_complete(fd, LAST_MARKER);
         }
 
-        Self { cfg, fd, state }
+        // The screen was just cleared to white
+        let shadow = vec![255; (state.screen_width * state.screen_height) as usize];
+
+        Self {
+            cfg,
+            fd,
+            state,
+            panel: shadow.clone(),
+            levels: shadow.clone(),
+            shadow,
+            rotation: Rotation::Deg0,
+            touch: TouchTransform::new(&state, Rotation::Deg0),
+            full_refresh: false,
+            soft_invert: false,
+            fast_refresh: None,
+        }
     }
 
-    pub fn draw_rect(&mut self, rect: RectShape) {
-        if rect.rect.height() == 0.0 || rect.rect.width() == 0.0 {
-            warn!("Why does egui do this, width or height is 0");
-            return;
+    /// Width of the screen as egui sees it, after rotation
+    pub fn width(&self) -> usize {
+        self.logical_size()[0]
+    }
+
+    pub fn height(&self) -> usize {
+        self.logical_size()[1]
+    }
+
+    fn logical_size(&self) -> [usize; 2] {
+        self.rotation.logical_size([
+            self.state.screen_width as usize,
+            self.state.screen_height as usize,
+        ])
+    }
+
+    /// Like `frost` for a Kobo Forma
+    pub fn device_codename(&self) -> String {
+        x8_to_string(self.state.device_codename)
+    }
+
+    pub fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+
+    /// Lays the buffers out for the new rotation. What is on the screen can't be reused,
+    /// so the next frame is drawn from scratch and flashed
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        debug!("Rotating the screen to {:?}", rotation);
+        self.rotation = rotation;
+        self.touch = TouchTransform::new(&self.state, rotation);
+        let len = self.width() * self.height();
+        self.shadow = vec![255; len];
+        self.panel = vec![255; len];
+        self.levels = vec![255; len];
+        self.full_refresh = true;
+    }
+
+    /// Inverts what is pushed to the screen, in the EPDC when it can do it
+    pub fn set_inverted(&mut self, inverted: bool) {
+        if self.state.can_hw_invert {
+            debug!("Setting hardware night mode to {}", inverted);
+            self.cfg.is_nightmode = inverted;
+            self.soft_invert = false;
+        } else {
+            debug!("Setting software night mode to {}", inverted);
+            self.soft_invert = inverted;
+        }
+        self.full_refresh = true;
+    }
+
+    /// Ghosts a bit, but doesn't flash and takes a fraction of the time. Only `area` (in
+    /// pixels) gets it, the rest of the frame is refreshed as usual
+    pub fn request_fast_refresh(&mut self, area: Rect) {
+        self.fast_refresh = Some(self.fast_refresh.map_or(area, |fast| fast.union(area)));
+    }
+
+    pub fn request_full_refresh(&mut self) {
+        self.full_refresh = true;
+    }
+
+    /// Has FBInk look at the framebuffer again, Nickel or a USB session may have changed its
+    /// rotation or bit depth, and so may a resume. Returns whether anything changed, the next
+    /// `present` then pushes and flashes the whole screen
+    pub fn reinit(&mut self) -> bool {
+        let lock = FBINK_LOCK.lock().unwrap();
+        let result = unsafe { fbink_reinit(self.fd, &self.cfg) };
+        if result < 0 {
+            error!("Failed to reinit fbink");
+            return false;
         }
-        let stroke_color = Rgb888::new(
-            rect.stroke.color.r(),
-            rect.stroke.color.g(),
-            rect.stroke.color.b(),
+        let changes = result as u32
+            & (OK_BPP_CHANGE | OK_ROTA_CHANGE | OK_LAYOUT_CHANGE | OK_GRAYSCALE_CHANGE);
+        if changes == 0 {
+            return false;
+        }
+        unsafe { fbink_get_state(&self.cfg, &mut self.state) };
+        drop(lock);
+        debug!(
+            "The framebuffer changed (bit depth: {}, rotation: {}, layout: {}, grayscale: {}), now {}x{} in {:?}",
+            changes & OK_BPP_CHANGE != 0,
+            changes & OK_ROTA_CHANGE != 0,
+            changes & OK_LAYOUT_CHANGE != 0,
+            changes & OK_GRAYSCALE_CHANGE != 0,
+            self.state.screen_width,
+            self.state.screen_height,
+            PixelFormat::from_bpp(self.state.bpp)
         );
-        let fill_color = Rgb888::new(rect.fill.r(), rect.fill.g(), rect.fill.b());
-
-        let style = PrimitiveStyleBuilder::new()
-            .stroke_width(rect.stroke.width as u32)
-            .stroke_color(stroke_color)
-            .fill_color(fill_color)
-            .build();
-
-        RoundedRectangle::with_equal_corners(
-            Rectangle::new(
-                Point::new(rect.rect.left() as i32, rect.rect.top() as i32),
-                Size::new(rect.rect.width() as u32, rect.rect.height() as u32),
-            ),
-            Size::new(rect.rounding.ne as u32, rect.rounding.ne as u32),
-        )
-        .into_styled(style)
-        .draw(self)
-        .expect("Failed to draw rounded rect");
 
-        unsafe {
-            let mut cls_rect: FBInkRect = std::mem::zeroed();
-            cls_rect.left = rect.rect.left() as u16;
-            cls_rect.top = rect.rect.top() as u16;
-            cls_rect.width = rect.rect.width() as u16;
-            cls_rect.height = rect.rect.height() as u16;
-            if fbink_refresh_rect(self.fd, &cls_rect, &self.cfg) < 0 {
-                error!(
-                    "Failed to refresh direct rect:  {} {} {} {}",
-                    cls_rect.left, cls_rect.top, cls_rect.width, cls_rect.height
-                );
-            }
+        // The size may have changed, and what is on the screen can't be trusted anymore
+        self.touch = TouchTransform::new(&self.state, self.rotation);
+        let len = self.width() * self.height();
+        self.shadow = vec![255; len];
+        self.panel = vec![UNKNOWN_LEVEL; len];
+        self.levels = vec![255; len];
+        self.full_refresh = true;
+        true
+    }
+
+    /// Blocks until the last refresh is on the screen
+    pub fn wait_for_complete(&self) {
+        let _lock = FBINK_LOCK.lock().unwrap();
+        unsafe { fbink_wait_for_complete(self.fd, LAST_MARKER) };
+    }
+
+    /// Starts a new frame, egui repaints everything so the shadow buffer starts out blank
+    pub fn clear(&mut self, gray: u8) {
+        self.shadow.fill(gray);
+    }
+
+    pub fn draw_mesh(
+        &mut self,
+        mesh: &Mesh,
+        clip_rect: Rect,
+        pixels_per_point: f32,
+        texture: &Texture,
+    ) {
+        raster::draw_mesh(mesh, clip_rect, pixels_per_point, texture, |x, y, color| {
+            self.blend_pixel(x, y, color)
+        });
+    }
+
+    /// Composites a premultiplied color over what is already in the shadow buffer
+    pub fn blend_pixel(&mut self, x: i32, y: i32, color: Color32) {
+        let width = self.width() as i32;
+        let height = self.height() as i32;
+        if x < 0 || y < 0 || x >= width || y >= height {
+            return;
         }
+        let index = (y * width + x) as usize;
+        let under = self.shadow[index] as u32;
+        let over = luminance(color) as u32;
+        self.shadow[index] = (over + (under * (255 - color.a() as u32) + 127) / 255).min(255) as u8;
     }
 
-    pub fn draw_text(&mut self, text: TextShape) {
-        unsafe {
-            let mut fbink_ot: FBInkOTConfig = std::mem::zeroed();
-            let mut fbink_ot_fit: FBInkOTFit = std::mem::zeroed();
-            if let Some(c) = text.override_text_color {
-                let mut font_fb_config: FBInkConfig = self.cfg;
-                font_fb_config.fg_color = rgb_to_gray(c.r(), c.g(), c.b());
-                fbink_update_pen_colors(&font_fb_config);
+    /// Pushes the part of the shadow buffer that changed since the last call to the screen.
+    /// `regions` (in pixels) override the dithering used for the rest of the screen
+    pub fn present(&mut self, dithering: Dithering, regions: &[(Rect, Dithering)]) {
+        let [width, height] = self.logical_size();
+        dither::quantize(&self.shadow, &mut self.levels, width, [0, 0, width, height], dithering);
+        let screen = Rect::from_min_size(Pos2::ZERO, Vec2::new(width as f32, height as f32));
+        for (rect, dithering) in regions {
+            let rect = rect.intersect(screen);
+            if !rect.is_positive() {
+                continue;
+            }
+            let area = [
+                rect.min.x.floor(),
+                rect.min.y.floor(),
+                rect.max.x.ceil(),
+                rect.max.y.ceil(),
+            ]
+            .map(|v| v as usize);
+            dither::quantize(&self.shadow, &mut self.levels, width, area, *dithering);
+        }
+        let full = std::mem::take(&mut self.full_refresh);
+        let fast = self.fast_refresh.take();
+        let area = if full {
+            screen
+        } else {
+            let Some(area) = changed_area(&self.panel, &self.levels, width) else {
+                return;
+            };
+            area
+        };
+        let fast = fast
+            .map(|fast| fast.intersect(area))
+            .filter(|fast| !full && fast.is_positive());
+        if let Some(fast) = fast {
+            // Snapped out, so the parts around it don't overlap it
+            let fast = Rect::from_min_max(fast.min.floor(), fast.max.ceil());
+            self.blit(fast, false, true);
+            for rest in around(area, fast) {
+                self.blit(rest, false, false);
             }
+        } else {
+            self.blit(area, full, false);
+        }
+
+        std::mem::swap(&mut self.panel, &mut self.levels);
+    }
 
-            fbink_ot.margins.left = text.pos.x as i16;
-            fbink_ot.margins.top = text.pos.y as i16;
-            //fbink_ot.margins.right = 0;
-            //fbink_ot.margins.bottom = 0;
-            fbink_ot.size_px = text.galley.size().y as u16;
-            let cstr = CString::new(&*text.galley.text()).unwrap();
-            let cchar: *const ::std::os::raw::c_char = cstr.as_ptr();
-            if fbink_print_ot(self.fd, cchar, &fbink_ot, &self.cfg, &mut fbink_ot_fit) < 0 {
-                error!("Failed to print string");
+    /// Pushes `area` of the quantized frame to the screen, with the fast waveform if `fast`
+    fn blit(&self, area: Rect, full: bool, fast: bool) {
+        let [width, height] = self.logical_size();
+        let (left, top) = (area.min.x as usize, area.min.y as usize);
+        let (right, bottom) = (area.max.x as usize, area.max.y as usize);
+
+        // The blit goes to the framebuffer as it is, so the area is rotated on the way
+        let size = [width, height];
+        let [p_left, p_top, p_right, p_bottom] =
+            self.rotation.area_to_physical([left, top, right, bottom], size);
+        let p_width = p_right - p_left;
+        let mut data = vec![0; p_width * (p_bottom - p_top)];
+        for y in top..bottom {
+            for x in left..right {
+                let [px, py] = self.rotation.to_physical([x, y], size);
+                let level = self.panel_level(self.levels[y * width + x]);
+                data[(py - p_top) * p_width + px - p_left] = level;
             }
-            fbink_wait_for_complete(self.fd, LAST_MARKER);
+        }
 
-            if text.override_text_color.is_some() {
-                let font_fb_config: FBInkConfig = self.cfg;
-                fbink_update_pen_colors(&font_fb_config);
+        let lock = FBINK_LOCK.lock().unwrap();
+        // Background pixels are part of the frame too, they must not be skipped
+        let mut blit_cfg = self.cfg;
+        blit_cfg.is_bgless = false;
+        blit_cfg.is_flashing = full;
+        if fast {
+            // DU goes to black or white only, which is what the feedback is drawn with
+            blit_cfg.wfm_mode = WFM_MODE_INDEX_E_WFM_DU as WFM_MODE_INDEX_T;
+        }
+        let result = unsafe {
+            fbink_print_raw_data(
+                self.fd,
+                data.as_ptr(),
+                p_width as c_int,
+                (p_bottom - p_top) as c_int,
+                data.len(),
+                p_left as c_short,
+                p_top as c_short,
+                &blit_cfg,
+            )
+        };
+        if result < 0 {
+            warn!(
+                "Failed to blit {:?}, falling back to putting pixels one by one",
+                area
+            );
+            for y in top..bottom {
+                for x in left..right {
+                    let level = self.levels[y * width + x];
+                    if full || level != self.panel[y * width + x] {
+                        let [px, py] = self.rotation.to_physical([x, y], size);
+                        let level = self.panel_level(level);
+                        self.set_pixel(px, py, Color32::from_gray(level));
+                    }
+                }
             }
+            drop(lock);
+            self.refresh_area(area);
+        }
+    }
+
+    /// Something else drew in `area` of the logical screen, so the next `present` pushes it
+    /// again whatever the frame has there
+    pub fn forget_area(&mut self, area: Rect) {
+        let [width, height] = self.logical_size();
+        let area = area.intersect(Rect::from_min_size(
+            Pos2::ZERO,
+            Vec2::new(width as f32, height as f32),
+        ));
+        if !area.is_positive() {
+            return;
+        }
+        for y in area.min.y.floor() as usize..area.max.y.ceil() as usize {
+            let row = y * width;
+            self.panel[row + area.min.x.floor() as usize..row + area.max.x.ceil() as usize]
+                .fill(UNKNOWN_LEVEL);
         }
     }
 
-    pub fn draw_paths(&mut self, path: PathShape) {
-        let style = PrimitiveStyleBuilder::new()
-            .fill_color(Rgb888::new(path.fill.r(), path.fill.g(), path.fill.b()))
-            .stroke_color(Rgb888::new(
-                path.stroke.color.r(),
-                path.stroke.color.g(),
-                path.stroke.color.b(),
-            ))
-            .stroke_width(path.stroke.width as u32)
-            .build();
-
-        let mut points = Vec::with_capacity(path.points.len());
-        for p in path.points {
-            points.push(Point::new(p.x as i32, p.y as i32));
+    pub fn soft_inverted(&self) -> bool {
+        self.soft_invert
+    }
+
+    /// The level as it has to be written to the framebuffer
+    fn panel_level(&self, level: u8) -> u8 {
+        if self.soft_invert {
+            invert_byte(level)
+        } else {
+            level
         }
+    }
 
-        let poly_line = Polyline::new(&points)
-            .into_styled(style);
-        let poly_rect = poly_line.bounding_box();
-        poly_line.draw(self).expect("Failed to draw poly line");
+    /// Where `area` of the logical screen is in the framebuffer, `None` if it's off screen
+    pub fn to_framebuffer_area(&self, area: Rect) -> Option<Rect> {
+        let size = self.logical_size();
+        let area = area.intersect(Rect::from_min_size(
+            Pos2::ZERO,
+            Vec2::new(size[0] as f32, size[1] as f32),
+        ));
+        if !area.is_positive() {
+            return None;
+        }
+        let [left, top, right, bottom] = self.rotation.area_to_physical(
+            [
+                area.min.x.floor(),
+                area.min.y.floor(),
+                area.max.x.ceil(),
+                area.max.y.ceil(),
+            ]
+            .map(|v| v as usize),
+            size,
+        );
+        Some(Rect::from_min_max(
+            Pos2::new(left as f32, top as f32),
+            Pos2::new(right as f32, bottom as f32),
+        ))
+    }
 
-        if poly_rect.is_zero_sized() == true {
-            warn!("This poly line is zero sized");
+    /// Refreshes `area` of the logical screen
+    pub fn refresh_area(&self, area: Rect) {
+        let Some(area) = self.to_framebuffer_area(area) else {
             return;
+        };
+        // A single line can't be refreshed, so grow it into the pixel before
+        let mut area = area;
+        if area.width() < 2.0 && area.min.x >= 1.0 {
+            area.min.x -= 1.0;
         }
+        if area.height() < 2.0 && area.min.y >= 1.0 {
+            area.min.y -= 1.0;
+        }
+        let _lock = FBINK_LOCK.lock().unwrap();
         unsafe {
             let mut cls_rect: FBInkRect = std::mem::zeroed();
-            cls_rect.left = poly_rect.top_left.x as u16;
-            cls_rect.top = poly_rect.top_left.y as u16;
-            cls_rect.width = poly_rect.size.width as u16;
-            cls_rect.height = poly_rect.size.height as u16;
+            cls_rect.left = area.left() as u16;
+            cls_rect.top = area.top() as u16;
+            cls_rect.width = area.width() as u16;
+            cls_rect.height = area.height() as u16;
             if fbink_refresh_rect(self.fd, &cls_rect, &self.cfg) < 0 {
                 error!(
-                    "Failed to refresh direct rect:  {} {} {} {}",
+                    "Failed to refresh area: {} {} {} {}",
                     cls_rect.left, cls_rect.top, cls_rect.width, cls_rect.height
                 );
             }
         }
     }
 
-    pub fn set_pixel(&self, x: i32, y: i32, color: Rgb888) {
-        //debug!("Setting pixel at {}x{} with color {:?}", x, y, color);
-        unsafe {
-            fbink_put_pixel_rgba(
-                self.fd,
-                x as u16,
-                y as u16,
-                color.r(),
-                color.b(),
-                color.g(),
-                255,
-            );
+    /// What is on the screen right now, as the 16 grays the panel shows
+    pub fn screenshot(&self) -> ColorImage {
+        ColorImage {
+            size: self.logical_size(),
+            pixels: self.panel.iter().map(|level| Color32::from_gray(*level)).collect(),
+        }
+    }
 
-            /*
-            let mut cls_rect: FBInkRect = std::mem::zeroed();
-            cls_rect.left = x as u16;
-            cls_rect.top = y as u16;
-            cls_rect.width = 1;
-            cls_rect.height = 1;
-            fbink_fill_rect_rgba(self.fd, &self.cfg, &cls_rect, false, color.r(), color.b(), color.g(), 255);
-            fbink_wait_for_complete(self.fd, LAST_MARKER);
-            */
+    /// Saves what is on the screen to `path`, as PGM if it ends in `.pgm` and PNG otherwise
+    pub fn dump(&self, path: &Path) -> io::Result<()> {
+        self.save_gray(&self.panel, path)
+    }
+
+    /// Same as `dump`, but for the frame being drawn, before it's quantized
+    pub fn dump_shadow(&self, path: &Path) -> io::Result<()> {
+        self.save_gray(&self.shadow, path)
+    }
+
+    fn save_gray(&self, pixels: &[u8], path: &Path) -> io::Result<()> {
+        let [width, height] = self.logical_size().map(|v| v as u32);
+        debug!("Dumping {}x{} pixels to {}", width, height, path.display());
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("pgm")) {
+            let mut file = BufWriter::new(File::create(path)?);
+            write!(file, "P5\n{} {}\n255\n", width, height)?;
+            file.write_all(pixels)?;
+            return file.flush();
+        }
+        image::save_buffer_with_format(
+            path,
+            pixels,
+            width,
+            height,
+            image::ColorType::L8,
+            ImageFormat::Png,
+        )
+        .map_err(io::Error::other)
+    }
+
+    /// Writes a single pixel straight to the framebuffer, without refreshing it.
+    /// Slow, only used when the bulk blit fails
+    pub fn set_pixel(&self, x: usize, y: usize, color: Color32) {
+        let Some(format) = PixelFormat::from_bpp(self.state.bpp) else {
+            return;
+        };
+        let color = if self.state.inverted_grayscale {
+            // Such panels show 0 as white, only ever the case for the gray formats
+            Color32::from_gray(invert_byte(luminance(color)))
+        } else {
+            color
+        };
+        let bytes = format.pack(color);
+
+        let mut size = 0;
+        let buffer = unsafe { fbink_get_fb_pointer(self.fd, &mut size) };
+        if buffer.is_null() {
+            return;
+        }
+        let buffer = unsafe { std::slice::from_raw_parts_mut(buffer, size) };
+        let offset =
+            y * self.state.scanline_stride as usize + x * format.bits_per_pixel() as usize / 8;
+        if format == PixelFormat::Gray4 {
+            // Even pixels are in the high nibble
+            let Some(byte) = buffer.get_mut(offset) else {
+                return;
+            };
+            *byte = if x % 2 == 1 {
+                (*byte & 0xF0) | bytes[0]
+            } else {
+                (*byte & 0x0F) | (bytes[0] << 4)
+            };
+        } else {
+            let len = format.bits_per_pixel() as usize / 8;
+            if let Some(pixel) = buffer.get_mut(offset..offset + len) {
+                pixel.copy_from_slice(&bytes[..len]);
+            }
         }
     }
 }
@@ -248,11 +541,12 @@ impl DrawTarget for FBInkBackend {
     where
         I: IntoIterator<Item = Pixel<Self::Color>>,
     {
-        let width = self.state.screen_width as i32;
-        let height = self.state.screen_height as i32;
+        let width = self.width() as i32;
+        let height = self.height() as i32;
         for Pixel(coord, color) in pixels.into_iter() {
             if coord.x < width && coord.y < height && coord.x >= 0 && coord.y >= 0 {
-                self.set_pixel(coord.x, coord.y, color);
+                self.shadow[(coord.y * width + coord.x) as usize] =
+                    rgb_to_gray(color.r(), color.g(), color.b());
             }
         }
 
@@ -263,101 +557,21 @@ impl DrawTarget for FBInkBackend {
         // Clamp the rectangle coordinates to the valid range by determining
         // the intersection of the fill area and the visible display area
         // by using Rectangle::intersection.
-        let mut area = area.intersection(&self.bounding_box());
+        let area = area.intersection(&self.bounding_box());
 
-        // Do not send a draw rectangle command if the intersection size if zero.
         // The size is checked by using `Rectangle::bottom_right`, which returns `None`
         // if the size is zero.
-
         let bottom_right = if let Some(bottom_right) = area.bottom_right() {
             bottom_right
         } else {
             return Ok(());
         };
 
-        if area.size.width <= 1 || area.size.height <= 1 {
-            //warn!("Using bare pixels to draw this rect: {:?}", area);
-            // We need to do it manually because it won't work with rect below 1 px in a direction
-            for y in 0..area.size.height {
-                for x in 0..area.size.width {
-                    self.set_pixel(
-                        area.top_left.x + x as i32,
-                        area.top_left.y + y as i32,
-                        color,
-                    );
-                }
-            }
-            // We need to refresh this part of it manually because its putting pixels
-            // And we can't refresh a single line
-            let changer = 1;
-            let mut new_width = area.size.width + changer;
-            let mut new_height = area.size.height + changer;
-
-            if new_width + area.top_left.x as u32 > self.state.screen_width {
-                // debug!(
-                //     "Why are we here x {} {} {}",
-                //     new_width, area.top_left.x, self.state.screen_width
-                // );
-                if area.top_left.x - changer as i32 > 0 {
-                    area.top_left.x = area.top_left.x - changer as i32;
-                } else {
-                    new_width = new_width - changer;
-                }
-            }
-
-            if new_height + area.top_left.y as u32 > self.state.screen_height {
-                // debug!(
-                //     "Why are we here y {} {} {}",
-                //     new_height, area.top_left.y, self.state.screen_height
-                // );
-                if area.top_left.y - changer as i32 > 0 {
-                    area.top_left.y = area.top_left.y - changer as i32;
-                } else {
-                    new_height = new_height - changer;
-                }
-            }
-
-            if new_height >= 2 && new_width >= 2 {
-                unsafe {
-                    let mut cls_rect: FBInkRect = std::mem::zeroed();
-                    cls_rect.left = area.top_left.x as u16;
-                    cls_rect.top = area.top_left.y as u16;
-                    cls_rect.width = new_width as u16;
-                    cls_rect.height = new_height as u16;
-                    if fbink_refresh_rect(self.fd, &cls_rect, &self.cfg) < 0 {
-                        error!(
-                            "Failed to refresh via pixel wrapper: {} {} {} {}",
-                            area.top_left.x, area.top_left.y, new_width, new_height
-                        );
-                    }
-                };
-            } else {
-                warn!("Somehow, we are here: {} {}", new_height, new_width);
-            }
-        } else {
-            unsafe {
-                let mut cls_rect: FBInkRect = std::mem::zeroed();
-                cls_rect.left = area.top_left.x as u16;
-                cls_rect.top = area.top_left.y as u16;
-                cls_rect.width = area.size.width as u16;
-                cls_rect.height = area.size.height as u16;
-                fbink_fill_rect_rgba(
-                    self.fd,
-                    &self.cfg,
-                    &cls_rect,
-                    false,
-                    color.r(),
-                    color.g(),
-                    color.b(),
-                    255,
-                );
-                if fbink_wait_for_complete(self.fd, LAST_MARKER) < 0 {
-                    error!(
-                        "Failed to refresh via fill rect: {} {} {} {}",
-                        area.top_left.x, area.top_left.y, area.size.width, area.size.height
-                    );
-                }
-            }
+        let gray = rgb_to_gray(color.r(), color.g(), color.b());
+        let stride = self.width();
+        for y in area.top_left.y as usize..bottom_right.y as usize + 1 {
+            let start = y * stride + area.top_left.x as usize;
+            self.shadow[start..start + area.size.width as usize].fill(gray);
         }
 
         Ok(())
@@ -368,14 +582,44 @@ impl DrawTarget for FBInkBackend {
 
 impl OriginDimensions for FBInkBackend {
     fn size(&self) -> Size {
-        Size::new(self.state.screen_width, self.state.screen_height)
+        Size::new(self.width() as u32, self.height() as u32)
+    }
+}
+
+/// Bounding box of the pixels that differ between two frames
+fn changed_area(old: &[u8], new: &[u8], width: usize) -> Option<Rect> {
+    let mut area: Option<Rect> = None;
+    for (y, (old_row, new_row)) in old.chunks_exact(width).zip(new.chunks_exact(width)).enumerate() {
+        if old_row == new_row {
+            continue;
+        }
+        let left = old_row.iter().zip(new_row).position(|(o, n)| o != n).unwrap_or(0);
+        let right = width - old_row.iter().zip(new_row).rev().position(|(o, n)| o != n).unwrap_or(0);
+        let row = Rect::from_min_max(
+            Pos2::new(left as f32, y as f32),
+            Pos2::new(right as f32, (y + 1) as f32),
+        );
+        area = Some(area.map_or(row, |area| area.union(row)));
     }
+    area
 }
 
-pub fn rgb_to_gray(r: u8, g: u8, b: u8) -> u8 {
-    // 709 formula
-    let gray = (0.2126 * (r as f32) + 0.7152 * (g as f32) + 0.0722 * (b as f32)).round() as u8;
-    gray
+/// What is left of `area` once `hole` is cut out of it, as up to four rectangles
+fn around(area: Rect, hole: Rect) -> Vec<Rect> {
+    let hole = hole.intersect(area);
+    let parts = [
+        Rect::from_min_max(area.min, Pos2::new(area.max.x, hole.min.y)),
+        Rect::from_min_max(Pos2::new(area.min.x, hole.max.y), area.max),
+        Rect::from_min_max(
+            Pos2::new(area.min.x, hole.min.y),
+            Pos2::new(hole.min.x, hole.max.y),
+        ),
+        Rect::from_min_max(
+            Pos2::new(hole.max.x, hole.min.y),
+            Pos2::new(area.max.x, hole.max.y),
+        ),
+    ];
+    parts.into_iter().filter(|part| part.is_positive()).collect()
 }
 
 pub fn invert_byte(b: u8) -> u8 {
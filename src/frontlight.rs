@@ -0,0 +1,448 @@
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use egui::{Context, Id};
+use log::{debug, warn};
+
+/// Where the light levels end up. Levels are percents, from 0 to 100
+pub trait FrontlightBackend: Send {
+    fn set_brightness(&mut self, percent: u8) -> io::Result<()>;
+    /// Only called if `has_warmth`
+    fn set_warmth(&mut self, percent: u8) -> io::Result<()>;
+    fn has_warmth(&self) -> bool;
+    /// What the light is at now, if the hardware can tell
+    fn brightness(&self) -> Option<u8> {
+        None
+    }
+    /// Same as `brightness`, for the warm light
+    fn warmth(&self) -> Option<u8> {
+        None
+    }
+}
+
+/// A light driven through sysfs, like `/sys/class/backlight/mxc_msp430.0`
+pub struct SysfsFrontlight {
+    brightness: PathBuf,
+    max_brightness: u32,
+    warmth: Option<SysfsWarmth>,
+}
+
+struct SysfsWarmth {
+    path: PathBuf,
+    max: u32,
+    /// Higher values are colder
+    inverted: bool,
+}
+
+impl SysfsFrontlight {
+    /// `brightness` is the file to write to, its range is read from `max_brightness` next to it
+    pub fn new(brightness: impl Into<PathBuf>) -> Self {
+        let brightness = brightness.into();
+        let max_brightness = brightness
+            .parent()
+            .and_then(|dir| fs::read_to_string(dir.join("max_brightness")).ok())
+            .and_then(|max| max.trim().parse().ok())
+            .unwrap_or(100);
+        Self {
+            brightness,
+            max_brightness,
+            warmth: None,
+        }
+    }
+
+    /// Adds a warm light mixer, from 0 to `max`
+    pub fn with_warmth(mut self, path: impl Into<PathBuf>, max: u32, inverted: bool) -> Self {
+        self.warmth = Some(SysfsWarmth {
+            path: path.into(),
+            max,
+            inverted,
+        });
+        self
+    }
+}
+
+fn scale(percent: u8, max: u32) -> u32 {
+    (percent as u32 * max + 50) / 100
+}
+
+/// The reverse of `scale`
+fn unscale(value: u32, max: u32) -> u8 {
+    ((value.min(max) * 100 + max / 2) / max.max(1)) as u8
+}
+
+fn read_value(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+impl FrontlightBackend for SysfsFrontlight {
+    fn set_brightness(&mut self, percent: u8) -> io::Result<()> {
+        fs::write(
+            &self.brightness,
+            scale(percent, self.max_brightness).to_string(),
+        )
+    }
+
+    fn set_warmth(&mut self, percent: u8) -> io::Result<()> {
+        let Some(warmth) = &self.warmth else {
+            return Err(io::ErrorKind::Unsupported.into());
+        };
+        let mut value = scale(percent, warmth.max);
+        if warmth.inverted {
+            value = warmth.max - value;
+        }
+        fs::write(&warmth.path, value.to_string())
+    }
+
+    fn has_warmth(&self) -> bool {
+        self.warmth.is_some()
+    }
+
+    fn brightness(&self) -> Option<u8> {
+        let value = read_value(&self.brightness)?;
+        Some(unscale(value, self.max_brightness))
+    }
+
+    fn warmth(&self) -> Option<u8> {
+        let warmth = self.warmth.as_ref()?;
+        let mut value = read_value(&warmth.path)?.min(warmth.max);
+        if warmth.inverted {
+            value = warmth.max - value;
+        }
+        Some(unscale(value, warmth.max))
+    }
+}
+
+/// Older Kobos only have the `ntx_io` driver's ioctl, which can't be read back
+pub struct NtxFrontlight {
+    file: File,
+}
+
+impl NtxFrontlight {
+    const CM_FRONT_LIGHT_SET: libc::c_ulong = 241;
+
+    pub fn open() -> io::Result<Self> {
+        Ok(Self {
+            file: File::open("/dev/ntx_io")?,
+        })
+    }
+}
+
+impl FrontlightBackend for NtxFrontlight {
+    fn set_brightness(&mut self, percent: u8) -> io::Result<()> {
+        let result = unsafe {
+            libc::ioctl(
+                self.file.as_raw_fd(),
+                Self::CM_FRONT_LIGHT_SET as _,
+                percent as libc::c_ulong,
+            )
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn set_warmth(&mut self, _percent: u8) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    fn has_warmth(&self) -> bool {
+        false
+    }
+}
+
+/// Records what it's given instead of driving a light, for tests and desktop runs.
+/// Clones share the records
+#[derive(Clone, Default)]
+pub struct FakeFrontlight {
+    brightness: Arc<Mutex<Vec<u8>>>,
+    warmth: Option<Arc<Mutex<Vec<u8>>>>,
+}
+
+impl FakeFrontlight {
+    pub fn new(has_warmth: bool) -> Self {
+        Self {
+            brightness: Arc::default(),
+            warmth: has_warmth.then(Arc::default),
+        }
+    }
+
+    /// Every brightness set so far, in order
+    pub fn brightness_writes(&self) -> Vec<u8> {
+        self.brightness.lock().unwrap().clone()
+    }
+
+    pub fn warmth_writes(&self) -> Vec<u8> {
+        self.warmth
+            .as_ref()
+            .map_or_else(Vec::new, |warmth| warmth.lock().unwrap().clone())
+    }
+}
+
+impl FrontlightBackend for FakeFrontlight {
+    fn set_brightness(&mut self, percent: u8) -> io::Result<()> {
+        self.brightness.lock().unwrap().push(percent);
+        Ok(())
+    }
+
+    fn set_warmth(&mut self, percent: u8) -> io::Result<()> {
+        let Some(warmth) = &self.warmth else {
+            return Err(io::ErrorKind::Unsupported.into());
+        };
+        warmth.lock().unwrap().push(percent);
+        Ok(())
+    }
+
+    fn has_warmth(&self) -> bool {
+        self.warmth.is_some()
+    }
+
+    fn brightness(&self) -> Option<u8> {
+        self.brightness.lock().unwrap().last().copied()
+    }
+
+    fn warmth(&self) -> Option<u8> {
+        self.warmth.as_ref()?.lock().unwrap().last().copied()
+    }
+}
+
+/// How each Kobo drives its warm light, by the codename FBInk gives it. The mixers go
+/// from 0 to 10, the warmest at 0
+fn warmth_mixer(codename: &str) -> Option<&'static str> {
+    match codename {
+        "nova" | "storm" => Some("class/backlight/lm3630a_led/color"),
+        "frost" => Some("class/backlight/tlc5947_bl/color"),
+        "cadmus" | "io" => Some("class/leds/aw99703-bl_FL1/color"),
+        _ => None,
+    }
+}
+
+const WHITE_LIGHT: &str = "class/backlight/mxc_msp430.0/brightness";
+
+struct Shared {
+    backend: Box<dyn FrontlightBackend>,
+    /// `None` until set, if the backend can't read it back
+    brightness: Option<u8>,
+    warmth: Option<u8>,
+    /// Bumped by every change, so a ramp stops when something else sets the light
+    generation: u64,
+}
+
+/// The frontlight, shared by clones
+#[derive(Clone)]
+pub struct Frontlight {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Frontlight {
+    pub fn new(backend: impl FrontlightBackend + 'static) -> Self {
+        Self {
+            shared: Arc::new(Mutex::new(Shared {
+                brightness: backend.brightness(),
+                warmth: backend.warmth(),
+                backend: Box::new(backend),
+                generation: 0,
+            })),
+        }
+    }
+
+    /// Picks the backend for the device named `codename`, under `sysfs_root`
+    pub fn for_device(codename: &str, sysfs_root: &Path) -> Self {
+        let white = sysfs_root.join(WHITE_LIGHT);
+        if !white.exists() {
+            match NtxFrontlight::open() {
+                Ok(ntx) => {
+                    debug!("Driving the frontlight of {} through ntx_io", codename);
+                    return Self::new(ntx);
+                }
+                Err(err) => warn!("No frontlight found for {}: {}", codename, err),
+            }
+        }
+        let mut sysfs = SysfsFrontlight::new(white);
+        if let Some(mixer) = warmth_mixer(codename) {
+            sysfs = sysfs.with_warmth(sysfs_root.join(mixer), 10, true);
+        }
+        debug!(
+            "Driving the frontlight of {} through sysfs, with{} warmth",
+            codename,
+            if sysfs.has_warmth() { "" } else { "out" }
+        );
+        Self::new(sysfs)
+    }
+
+    /// `None` if the light can't be read back and wasn't set yet
+    pub fn brightness(&self) -> Option<u8> {
+        self.shared.lock().unwrap().brightness
+    }
+
+    /// `None` without a warm light, or like `brightness` if it's unknown
+    pub fn warmth(&self) -> Option<u8> {
+        self.shared.lock().unwrap().warmth
+    }
+
+    pub fn set_brightness(&self, percent: u8) -> io::Result<()> {
+        let mut shared = self.shared.lock().unwrap();
+        shared.generation += 1;
+        Self::write_brightness(&mut shared, percent)
+    }
+
+    pub fn set_warmth(&self, percent: u8) -> io::Result<()> {
+        let percent = percent.min(100);
+        let mut shared = self.shared.lock().unwrap();
+        if !shared.backend.has_warmth() {
+            return Err(io::ErrorKind::Unsupported.into());
+        }
+        shared.backend.set_warmth(percent)?;
+        shared.warmth = Some(percent);
+        Ok(())
+    }
+
+    /// Fades the brightness to `percent` over `duration`, in a background thread
+    pub fn ramp_brightness(&self, percent: u8, duration: Duration) {
+        let percent = percent.min(100);
+        let (start, generation) = {
+            let mut shared = self.shared.lock().unwrap();
+            shared.generation += 1;
+            // Straight to the target if there is nothing to fade from
+            (shared.brightness.unwrap_or(percent), shared.generation)
+        };
+        // One percent at a time, but not faster than the light driver can follow
+        let steps = (start.abs_diff(percent) as u32)
+            .min(duration.as_millis() as u32 / 16)
+            .max(1);
+        let interval = duration / steps;
+        let shared = self.shared.clone();
+        thread::spawn(move || {
+            for step in 1..=steps {
+                thread::sleep(interval);
+                let mut shared = shared.lock().unwrap();
+                if shared.generation != generation {
+                    return;
+                }
+                let level =
+                    start as i32 + (percent as i32 - start as i32) * step as i32 / steps as i32;
+                if let Err(err) = Self::write_brightness(&mut shared, level as u8) {
+                    warn!("Failed to ramp the frontlight: {}", err);
+                    return;
+                }
+            }
+        });
+    }
+
+    fn write_brightness(shared: &mut Shared, percent: u8) -> io::Result<()> {
+        let percent = percent.min(100);
+        shared.backend.set_brightness(percent)?;
+        shared.brightness = Some(percent);
+        Ok(())
+    }
+}
+
+fn frontlight_id() -> Id {
+    Id::new("egui_fbink_frontlight")
+}
+
+/// The device's frontlight, set up by the runner
+pub fn frontlight(ctx: &Context) -> Option<Frontlight> {
+    ctx.data(|data| data.get_temp(frontlight_id()))
+}
+
+pub(crate) fn install(ctx: &Context, frontlight: Frontlight) {
+    ctx.data_mut(|data| data.insert_temp(frontlight_id(), frontlight));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sets_and_clamps_brightness() {
+        let fake = FakeFrontlight::new(false);
+        let frontlight = Frontlight::new(fake.clone());
+        frontlight.set_brightness(40).unwrap();
+        frontlight.set_brightness(150).unwrap();
+        assert_eq!(fake.brightness_writes(), vec![40, 100]);
+        assert_eq!(frontlight.brightness(), Some(100));
+    }
+
+    #[test]
+    fn warmth_needs_a_warm_light() {
+        let frontlight = Frontlight::new(FakeFrontlight::new(false));
+        assert_eq!(frontlight.warmth(), None);
+        let err = frontlight.set_warmth(50).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+
+        let fake = FakeFrontlight::new(true);
+        let frontlight = Frontlight::new(fake.clone());
+        frontlight.set_warmth(30).unwrap();
+        assert_eq!(frontlight.warmth(), Some(30));
+        assert_eq!(fake.warmth_writes(), vec![30]);
+    }
+
+    #[test]
+    fn ramp_reaches_its_target() {
+        let fake = FakeFrontlight::new(false);
+        let frontlight = Frontlight::new(fake.clone());
+        frontlight.ramp_brightness(10, Duration::from_millis(50));
+        thread::sleep(Duration::from_millis(300));
+        let writes = fake.brightness_writes();
+        assert_eq!(writes.last(), Some(&10));
+        assert!(writes.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(frontlight.brightness(), Some(10));
+    }
+
+    #[test]
+    fn unknown_until_set() {
+        let frontlight = Frontlight::new(FakeFrontlight::new(true));
+        assert_eq!(frontlight.brightness(), None);
+        assert_eq!(frontlight.warmth(), None);
+        frontlight.set_brightness(20).unwrap();
+        assert_eq!(frontlight.brightness(), Some(20));
+    }
+
+    #[test]
+    fn setting_the_brightness_stops_a_ramp() {
+        let fake = FakeFrontlight::new(false);
+        let frontlight = Frontlight::new(fake.clone());
+        frontlight.ramp_brightness(100, Duration::from_secs(1));
+        frontlight.set_brightness(5).unwrap();
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(fake.brightness_writes(), vec![5]);
+    }
+
+    #[test]
+    fn sysfs_scales_inverts_and_reads_back() {
+        let root = std::env::temp_dir().join(format!("egui-fbink-light-{}", std::process::id()));
+        let white = root.join(WHITE_LIGHT);
+        let mixer = root.join(warmth_mixer("frost").unwrap());
+        fs::create_dir_all(white.parent().unwrap()).unwrap();
+        fs::create_dir_all(mixer.parent().unwrap()).unwrap();
+        fs::write(white.parent().unwrap().join("max_brightness"), "100\n").unwrap();
+        fs::write(&white, "45\n").unwrap();
+        fs::write(&mixer, "2\n").unwrap();
+
+        let frontlight = Frontlight::for_device("frost", &root);
+        let read_back = (frontlight.brightness(), frontlight.warmth());
+        frontlight.set_brightness(60).unwrap();
+        frontlight.set_warmth(30).unwrap();
+        let brightness = fs::read_to_string(&white).unwrap();
+        let warmth = fs::read_to_string(&mixer).unwrap();
+        let _ = fs::remove_dir_all(&root);
+
+        assert_eq!(read_back, (Some(45), Some(80)));
+        assert_eq!(brightness, "60");
+        // 3 out of 10, the warmest being 0
+        assert_eq!(warmth, "7");
+    }
+
+    #[test]
+    fn profiles_by_codename() {
+        assert!(warmth_mixer("frost").is_some());
+        assert!(warmth_mixer("cadmus").is_some());
+        assert!(warmth_mixer("kraken").is_none());
+    }
+}
@@ -0,0 +1,546 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use egui::{
+    Context, Event, Id, Modifiers, PointerButton, Pos2, TouchDeviceId, TouchId, TouchPhase,
+};
+use log::debug;
+
+/// Thresholds of the gesture recognizer. Distances are on the panel, in millimeters
+#[derive(Clone, Debug)]
+pub struct GestureOptions {
+    /// How long a finger stays down, without moving, for a secondary click
+    pub long_press: Duration,
+    /// How far a finger can wander and still tap or long press
+    pub slop_mm: f32,
+    /// Shortest swipe
+    pub swipe_min_mm: f32,
+    /// Slower than this it's a drag, not a swipe
+    pub swipe_max_duration: Duration,
+    /// How much the distance between two fingers changes before it's a pinch
+    pub pinch_min_mm: f32,
+}
+
+impl Default for GestureOptions {
+    fn default() -> Self {
+        Self {
+            long_press: Duration::from_millis(500),
+            slop_mm: 2.0,
+            swipe_min_mm: 15.0,
+            swipe_max_duration: Duration::from_millis(500),
+            pinch_min_mm: 5.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SwipeDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// Positions are in points
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Gesture {
+    /// Also sent to egui as a secondary click
+    LongPress { pos: Pos2 },
+    Swipe {
+        direction: SwipeDirection,
+        from: Pos2,
+        to: Pos2,
+    },
+    /// `scale` is the change since the last pinch gesture, above 1 when the fingers spread
+    Pinch { center: Pos2, scale: f32 },
+}
+
+fn gestures_id() -> Id {
+    Id::new("egui_fbink_gestures")
+}
+
+/// The gestures recognized from the touches handed to this frame
+pub fn gestures(ctx: &Context) -> Vec<Gesture> {
+    ctx.data(|data| data.get_temp(gestures_id()).unwrap_or_default())
+}
+
+pub(crate) fn store(ctx: &Context, gestures: Vec<Gesture>) {
+    ctx.data_mut(|data| data.insert_temp(gestures_id(), gestures));
+}
+
+struct Finger {
+    origin: Pos2,
+    pos: Pos2,
+    start: Instant,
+    /// Was part of a pinch, so it can't swipe anymore
+    pinched: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Press {
+    /// Could still be a tap or a long press, egui hasn't heard of it yet
+    Pending,
+    Dragging,
+    LongPressed,
+}
+
+/// Sits between the touch panel and egui. The first finger drives egui's pointer, but
+/// its press is only sent once it's clear it isn't a long press: when it moves, goes up,
+/// or becomes a secondary click
+pub(crate) struct GestureRecognizer {
+    options: GestureOptions,
+    fingers: BTreeMap<u64, Finger>,
+    primary: Option<(u64, Press)>,
+    /// Distance between the two fingers when the last pinch was reported
+    pinch: Option<f32>,
+    gestures: Vec<Gesture>,
+}
+
+impl GestureRecognizer {
+    pub fn new(options: GestureOptions) -> Self {
+        Self {
+            options,
+            fingers: BTreeMap::new(),
+            primary: None,
+            pinch: None,
+            gestures: Vec::new(),
+        }
+    }
+
+    /// `pos` is in points, on the logical screen. `points_per_mm` converts the thresholds
+    pub fn touch(&mut self, id: u64, phase: TouchPhase, pos: Pos2, points_per_mm: f32) -> Vec<Event> {
+        let mut events = vec![Event::Touch {
+            device_id: TouchDeviceId(0),
+            id: TouchId(id),
+            phase,
+            pos,
+            force: None,
+        }];
+        let slop = self.options.slop_mm * points_per_mm;
+
+        match phase {
+            TouchPhase::Start => {
+                self.fingers.insert(
+                    id,
+                    Finger {
+                        origin: pos,
+                        pos,
+                        start: Instant::now(),
+                        pinched: false,
+                    },
+                );
+                if self.fingers.len() == 1 {
+                    self.primary = Some((id, Press::Pending));
+                } else {
+                    // A second finger makes it a pinch, the pointer lets go
+                    if let Some((primary, press)) = self.primary.take() {
+                        let primary_pos = self.fingers[&primary].pos;
+                        if press == Press::Dragging {
+                            events.push(button(PointerButton::Primary, primary_pos, false));
+                        }
+                        if press != Press::Pending {
+                            events.push(Event::PointerGone);
+                        }
+                    }
+                    self.pinch = self.finger_distance();
+                    for finger in self.fingers.values_mut() {
+                        finger.pinched = true;
+                    }
+                }
+            }
+            TouchPhase::Move => {
+                if let Some(finger) = self.fingers.get_mut(&id) {
+                    finger.pos = pos;
+                }
+                match self.primary {
+                    Some((primary, Press::Pending)) if primary == id => {
+                        let origin = self.fingers[&id].origin;
+                        if origin.distance(pos) > slop {
+                            self.primary = Some((id, Press::Dragging));
+                            events.extend([
+                                Event::PointerMoved(origin),
+                                button(PointerButton::Primary, origin, true),
+                                Event::PointerMoved(pos),
+                            ]);
+                        }
+                    }
+                    Some((primary, Press::Dragging)) if primary == id => {
+                        events.push(Event::PointerMoved(pos));
+                    }
+                    _ => {}
+                }
+                self.recognize_pinch(self.options.pinch_min_mm * points_per_mm);
+            }
+            TouchPhase::End | TouchPhase::Cancel => {
+                let finger = self.fingers.remove(&id);
+                if let Some((primary, press)) = self.primary {
+                    if primary == id {
+                        self.primary = None;
+                        if phase == TouchPhase::End {
+                            match press {
+                                Press::Pending => events.extend([
+                                    Event::PointerMoved(pos),
+                                    button(PointerButton::Primary, pos, true),
+                                    button(PointerButton::Primary, pos, false),
+                                ]),
+                                Press::Dragging => {
+                                    events.push(button(PointerButton::Primary, pos, false))
+                                }
+                                Press::LongPressed => {}
+                            }
+                        }
+                        events.push(Event::PointerGone);
+                    }
+                }
+                if phase == TouchPhase::End {
+                    if let Some(finger) = finger {
+                        self.recognize_swipe(&finger, pos, points_per_mm);
+                    }
+                }
+                if self.fingers.len() < 2 {
+                    self.pinch = None;
+                }
+            }
+        }
+        events
+    }
+
+    /// Turns a finger held still into a secondary click, once it's been down long enough
+    pub fn poll(&mut self) -> Vec<Event> {
+        let Some((id, Press::Pending)) = self.primary else {
+            return Vec::new();
+        };
+        let finger = &self.fingers[&id];
+        if finger.start.elapsed() < self.options.long_press {
+            return Vec::new();
+        }
+        debug!("Long press at {:?}", finger.pos);
+        self.primary = Some((id, Press::LongPressed));
+        self.gestures.push(Gesture::LongPress { pos: finger.pos });
+        vec![
+            Event::PointerMoved(finger.pos),
+            button(PointerButton::Secondary, finger.pos, true),
+            button(PointerButton::Secondary, finger.pos, false),
+        ]
+    }
+
+    /// When `poll` has to be called again for a long press
+    pub fn next_deadline(&self) -> Option<Duration> {
+        let Some((id, Press::Pending)) = self.primary else {
+            return None;
+        };
+        let elapsed = self.fingers[&id].start.elapsed();
+        Some(self.options.long_press.saturating_sub(elapsed))
+    }
+
+    pub fn take_gestures(&mut self) -> Vec<Gesture> {
+        std::mem::take(&mut self.gestures)
+    }
+
+    fn finger_distance(&self) -> Option<f32> {
+        let mut fingers = self.fingers.values();
+        match (fingers.next(), fingers.next()) {
+            (Some(a), Some(b)) => Some(a.pos.distance(b.pos)),
+            _ => None,
+        }
+    }
+
+    fn recognize_pinch(&mut self, min_change: f32) {
+        let (Some(last), Some(distance)) = (self.pinch, self.finger_distance()) else {
+            return;
+        };
+        if (distance - last).abs() < min_change || last <= 0.0 {
+            return;
+        }
+        let mut fingers = self.fingers.values();
+        let (Some(a), Some(b)) = (fingers.next(), fingers.next()) else {
+            return;
+        };
+        self.gestures.push(Gesture::Pinch {
+            center: a.pos.lerp(b.pos, 0.5),
+            scale: distance / last,
+        });
+        self.pinch = Some(distance);
+    }
+
+    fn recognize_swipe(&mut self, finger: &Finger, to: Pos2, points_per_mm: f32) {
+        if finger.pinched || finger.start.elapsed() > self.options.swipe_max_duration {
+            return;
+        }
+        let delta = to - finger.origin;
+        if delta.length() < self.options.swipe_min_mm * points_per_mm {
+            return;
+        }
+        let direction = if delta.x.abs() > delta.y.abs() {
+            if delta.x > 0.0 {
+                SwipeDirection::Right
+            } else {
+                SwipeDirection::Left
+            }
+        } else if delta.y > 0.0 {
+            SwipeDirection::Down
+        } else {
+            SwipeDirection::Up
+        };
+        debug!("Swipe {:?} of {:?}", direction, delta);
+        self.gestures.push(Gesture::Swipe {
+            direction,
+            from: finger.origin,
+            to,
+        });
+    }
+}
+
+fn button(button: PointerButton, pos: Pos2, pressed: bool) -> Event {
+    Event::PointerButton {
+        pos,
+        button,
+        pressed,
+        modifiers: Modifiers::NONE,
+    }
+}
+
+/// Converts millimeters on a panel of `dpi` to points, drawn at `pixels_per_point`
+pub(crate) fn points_per_mm(dpi: f32, pixels_per_point: f32) -> f32 {
+    let dpi = if dpi > 0.0 { dpi } else { 160.0 };
+    dpi / 25.4 / pixels_per_point
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One point per millimeter, so the thresholds read as points
+    const PPM: f32 = 1.0;
+
+    fn recognizer(options: GestureOptions) -> GestureRecognizer {
+        GestureRecognizer::new(options)
+    }
+
+    /// What egui gets besides the raw touches
+    fn pointer(events: Vec<Event>) -> Vec<Event> {
+        events
+            .into_iter()
+            .filter(|event| !matches!(event, Event::Touch { .. }))
+            .collect()
+    }
+
+    fn pos(x: f32, y: f32) -> Pos2 {
+        Pos2::new(x, y)
+    }
+
+    #[test]
+    fn tap_is_sent_on_release() {
+        let mut gestures = recognizer(GestureOptions::default());
+        let start = gestures.touch(1, TouchPhase::Start, pos(10.0, 10.0), PPM);
+        assert_eq!(start.len(), 1);
+        assert!(matches!(start[0], Event::Touch { .. }));
+
+        let end = gestures.touch(1, TouchPhase::End, pos(10.5, 10.0), PPM);
+        assert_eq!(
+            pointer(end),
+            vec![
+                Event::PointerMoved(pos(10.5, 10.0)),
+                button(PointerButton::Primary, pos(10.5, 10.0), true),
+                button(PointerButton::Primary, pos(10.5, 10.0), false),
+                Event::PointerGone,
+            ]
+        );
+        assert!(gestures.take_gestures().is_empty());
+    }
+
+    #[test]
+    fn moving_within_the_slop_stays_pending() {
+        let mut gestures = recognizer(GestureOptions::default());
+        gestures.touch(1, TouchPhase::Start, pos(10.0, 10.0), PPM);
+        let moved = gestures.touch(1, TouchPhase::Move, pos(11.5, 10.0), PPM);
+        assert!(pointer(moved).is_empty());
+        assert!(gestures.next_deadline().is_some());
+    }
+
+    #[test]
+    fn drag_presses_where_it_started() {
+        let mut gestures = recognizer(GestureOptions::default());
+        gestures.touch(1, TouchPhase::Start, pos(10.0, 10.0), PPM);
+        let moved = gestures.touch(1, TouchPhase::Move, pos(15.0, 10.0), PPM);
+        assert_eq!(
+            pointer(moved),
+            vec![
+                Event::PointerMoved(pos(10.0, 10.0)),
+                button(PointerButton::Primary, pos(10.0, 10.0), true),
+                Event::PointerMoved(pos(15.0, 10.0)),
+            ]
+        );
+        assert_eq!(gestures.next_deadline(), None);
+
+        let moved = gestures.touch(1, TouchPhase::Move, pos(16.0, 10.0), PPM);
+        assert_eq!(pointer(moved), vec![Event::PointerMoved(pos(16.0, 10.0))]);
+
+        let end = gestures.touch(1, TouchPhase::End, pos(16.0, 10.0), PPM);
+        assert_eq!(
+            pointer(end),
+            vec![
+                button(PointerButton::Primary, pos(16.0, 10.0), false),
+                Event::PointerGone,
+            ]
+        );
+        // 6 mm is too short for a swipe
+        assert!(gestures.take_gestures().is_empty());
+    }
+
+    #[test]
+    fn long_press_waits_for_its_deadline() {
+        let mut gestures = recognizer(GestureOptions {
+            long_press: Duration::from_secs(3600),
+            ..Default::default()
+        });
+        gestures.touch(1, TouchPhase::Start, pos(10.0, 10.0), PPM);
+        assert!(gestures.poll().is_empty());
+        assert!(gestures.next_deadline().unwrap() > Duration::from_secs(3500));
+        assert!(gestures.take_gestures().is_empty());
+    }
+
+    #[test]
+    fn long_press_is_a_secondary_click() {
+        let mut gestures = recognizer(GestureOptions {
+            long_press: Duration::ZERO,
+            ..Default::default()
+        });
+        gestures.touch(1, TouchPhase::Start, pos(10.0, 10.0), PPM);
+        assert_eq!(gestures.next_deadline(), Some(Duration::ZERO));
+        assert_eq!(
+            gestures.poll(),
+            vec![
+                Event::PointerMoved(pos(10.0, 10.0)),
+                button(PointerButton::Secondary, pos(10.0, 10.0), true),
+                button(PointerButton::Secondary, pos(10.0, 10.0), false),
+            ]
+        );
+        assert_eq!(
+            gestures.take_gestures(),
+            vec![Gesture::LongPress {
+                pos: pos(10.0, 10.0)
+            }]
+        );
+        assert_eq!(gestures.next_deadline(), None);
+        assert!(gestures.poll().is_empty());
+
+        // No primary click once the finger lifts
+        let end = gestures.touch(1, TouchPhase::End, pos(10.0, 10.0), PPM);
+        assert_eq!(pointer(end), vec![Event::PointerGone]);
+    }
+
+    #[test]
+    fn swipe_directions() {
+        let cases = [
+            (pos(120.0, 100.0), SwipeDirection::Right),
+            (pos(80.0, 100.0), SwipeDirection::Left),
+            (pos(100.0, 120.0), SwipeDirection::Down),
+            (pos(100.0, 80.0), SwipeDirection::Up),
+            // The longer axis wins
+            (pos(118.0, 80.0), SwipeDirection::Up),
+        ];
+        for (to, direction) in cases {
+            let mut gestures = recognizer(GestureOptions {
+                swipe_max_duration: Duration::from_secs(3600),
+                ..Default::default()
+            });
+            gestures.touch(1, TouchPhase::Start, pos(100.0, 100.0), PPM);
+            gestures.touch(1, TouchPhase::Move, to, PPM);
+            gestures.touch(1, TouchPhase::End, to, PPM);
+            assert_eq!(
+                gestures.take_gestures(),
+                vec![Gesture::Swipe {
+                    direction,
+                    from: pos(100.0, 100.0),
+                    to,
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn swipe_thresholds() {
+        let options = GestureOptions {
+            swipe_min_mm: 15.0,
+            swipe_max_duration: Duration::from_secs(3600),
+            ..Default::default()
+        };
+        let mut gestures = recognizer(options.clone());
+        gestures.touch(1, TouchPhase::Start, pos(100.0, 100.0), PPM);
+        gestures.touch(1, TouchPhase::End, pos(114.0, 100.0), PPM);
+        assert!(gestures.take_gestures().is_empty());
+
+        // Twice the points per millimeter, 14 points are only 7 mm
+        let mut gestures = recognizer(options);
+        gestures.touch(1, TouchPhase::Start, pos(100.0, 100.0), 2.0);
+        gestures.touch(1, TouchPhase::End, pos(120.0, 100.0), 2.0);
+        assert!(gestures.take_gestures().is_empty());
+
+        let mut gestures = recognizer(GestureOptions {
+            swipe_max_duration: Duration::from_millis(1),
+            ..Default::default()
+        });
+        gestures.touch(1, TouchPhase::Start, pos(100.0, 100.0), PPM);
+        std::thread::sleep(Duration::from_millis(5));
+        gestures.touch(1, TouchPhase::End, pos(150.0, 100.0), PPM);
+        assert!(gestures.take_gestures().is_empty(), "too slow for a swipe");
+    }
+
+    #[test]
+    fn pinch_reports_the_scale_since_the_last_one() {
+        let mut gestures = recognizer(GestureOptions {
+            pinch_min_mm: 5.0,
+            swipe_max_duration: Duration::from_secs(3600),
+            ..Default::default()
+        });
+        gestures.touch(1, TouchPhase::Start, pos(0.0, 0.0), PPM);
+        let second = gestures.touch(2, TouchPhase::Start, pos(10.0, 0.0), PPM);
+        // The first finger was still pending, egui never saw it
+        assert!(pointer(second).is_empty());
+
+        gestures.touch(2, TouchPhase::Move, pos(13.0, 0.0), PPM);
+        assert!(gestures.take_gestures().is_empty());
+
+        gestures.touch(2, TouchPhase::Move, pos(20.0, 0.0), PPM);
+        assert_eq!(
+            gestures.take_gestures(),
+            vec![Gesture::Pinch {
+                center: pos(10.0, 0.0),
+                scale: 2.0,
+            }]
+        );
+
+        gestures.touch(2, TouchPhase::Move, pos(10.0, 0.0), PPM);
+        assert_eq!(
+            gestures.take_gestures(),
+            vec![Gesture::Pinch {
+                center: pos(5.0, 0.0),
+                scale: 0.5,
+            }]
+        );
+
+        // Fingers that pinched don't swipe when they lift
+        gestures.touch(2, TouchPhase::End, pos(40.0, 0.0), PPM);
+        gestures.touch(1, TouchPhase::End, pos(0.0, 0.0), PPM);
+        assert!(gestures.take_gestures().is_empty());
+    }
+
+    #[test]
+    fn second_finger_lets_go_of_a_drag() {
+        let mut gestures = recognizer(GestureOptions::default());
+        gestures.touch(1, TouchPhase::Start, pos(10.0, 10.0), PPM);
+        gestures.touch(1, TouchPhase::Move, pos(15.0, 10.0), PPM);
+        let second = gestures.touch(2, TouchPhase::Start, pos(30.0, 10.0), PPM);
+        assert_eq!(
+            pointer(second),
+            vec![
+                button(PointerButton::Primary, pos(15.0, 10.0), false),
+                Event::PointerGone,
+            ]
+        );
+        // The pointer is gone for good, lifting the first finger clicks nothing
+        let end = gestures.touch(1, TouchPhase::End, pos(15.0, 10.0), PPM);
+        assert!(pointer(end).is_empty());
+    }
+}
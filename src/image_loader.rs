@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use egui::load::{
+    Bytes, BytesLoadResult, BytesLoader, BytesPoll, ImageLoadResult, ImageLoader, ImagePoll,
+    LoadError, SizeHint,
+};
+use egui::{Color32, ColorImage, Context, Id};
+use image::imageops::FilterType;
+use image::ImageFormat;
+use log::{debug, warn};
+
+use crate::dither::{self, Dithering};
+
+const FILE_LOADER_ID: &str = "egui_fbink::FileLoader";
+const IMAGE_LOADER_ID: &str = "egui_fbink::GrayImageLoader";
+
+#[derive(Clone, Copy, Debug)]
+pub struct ImageLoaderOptions {
+    /// How many bytes the decoded images kept by the loader may take together. Images that
+    /// weren't shown for the longest time are dropped first, so it should at least fit one
+    /// screen of them. Their textures aren't counted: egui and the runner each keep a copy,
+    /// so images take about three times this in the end
+    pub memory_budget: usize,
+    /// Applied to the gray values, above 1.0 brightens the mid tones which e-ink shows too dark
+    pub gamma: f32,
+    /// Done once when decoding, at the size the image is shown at
+    pub dithering: Dithering,
+}
+
+impl Default for ImageLoaderOptions {
+    fn default() -> Self {
+        Self {
+            // Enough for a screen full of covers, without hurting 256 MB devices
+            memory_budget: 32 * 1024 * 1024,
+            gamma: 1.0,
+            dithering: Dithering::FloydSteinberg,
+        }
+    }
+}
+
+/// Lets `egui::Image` show PNG, JPEG and BMP files from `file://` uris, decoded to gray.
+/// Can be called every frame, it only installs the loaders once
+pub fn install_image_loaders(ctx: &Context, options: ImageLoaderOptions) {
+    if !ctx.is_loader_installed(FILE_LOADER_ID) {
+        ctx.add_bytes_loader(Arc::new(FileLoader));
+    }
+    if !ctx.is_loader_installed(IMAGE_LOADER_ID) {
+        let loader = Arc::new(GrayImageLoader::new(options));
+        ctx.add_image_loader(loader.clone());
+        ctx.data_mut(|data| data.insert_temp(image_loader_id(), loader));
+    }
+}
+
+fn image_loader_id() -> Id {
+    Id::new("egui_fbink_image_loader")
+}
+
+/// Loaders can't call back into the context while it runs them, so the textures of the
+/// images dropped from the cache are freed here, after the frame. `Context::forget_image`
+/// isn't used, it would drop the other sizes of the same uri from the cache as well
+pub(crate) fn forget_evicted(ctx: &Context) {
+    let Some(loader) = ctx.data(|data| data.get_temp::<Arc<GrayImageLoader>>(image_loader_id()))
+    else {
+        return;
+    };
+    let evicted = std::mem::take(&mut loader.cache.lock().unwrap().evicted);
+    if evicted.is_empty() {
+        return;
+    }
+    let loaders = ctx.loaders();
+    for uri in evicted {
+        debug!("Forgetting image {} to stay in the memory budget", uri);
+        // Textures are only cached by uri, one still in use is uploaded again from the cache
+        for texture_loader in loaders.texture.lock().iter() {
+            texture_loader.forget(&uri);
+        }
+    }
+}
+
+/// Reads `file://` uris. The bytes aren't kept, the decoded image is what gets cached
+struct FileLoader;
+
+impl BytesLoader for FileLoader {
+    fn id(&self) -> &str {
+        FILE_LOADER_ID
+    }
+
+    fn load(&self, _ctx: &Context, uri: &str) -> BytesLoadResult {
+        let Some(path) = uri.strip_prefix("file://") else {
+            return Err(LoadError::NotSupported);
+        };
+        match std::fs::read(path) {
+            Ok(bytes) => Ok(BytesPoll::Ready {
+                size: None,
+                bytes: Bytes::Shared(bytes.into()),
+                mime: None,
+            }),
+            Err(err) => Err(LoadError::Loading(format!("Failed to read {}: {}", path, err))),
+        }
+    }
+
+    fn forget(&self, _uri: &str) {}
+
+    fn forget_all(&self) {}
+
+    fn byte_size(&self) -> usize {
+        0
+    }
+}
+
+struct CachedImage {
+    image: Arc<ColorImage>,
+    last_used: usize,
+}
+
+#[derive(Default)]
+struct Cache {
+    images: HashMap<(String, SizeHint), CachedImage>,
+    bytes: usize,
+    frame: usize,
+    evicted: Vec<String>,
+}
+
+impl Cache {
+    fn insert(&mut self, key: (String, SizeHint), image: Arc<ColorImage>, budget: usize) {
+        self.bytes += image_bytes(&image);
+        self.images.insert(
+            key,
+            CachedImage {
+                image,
+                last_used: self.frame,
+            },
+        );
+
+        while self.bytes > budget {
+            // Never drop what is on screen right now
+            let oldest = self
+                .images
+                .iter()
+                .filter(|(_, cached)| cached.last_used < self.frame)
+                .min_by_key(|(_, cached)| cached.last_used)
+                .map(|(key, _)| key.clone());
+            let Some(oldest) = oldest else {
+                warn!("Images shown in this frame alone are over the memory budget");
+                break;
+            };
+            if let Some(cached) = self.images.remove(&oldest) {
+                self.bytes -= image_bytes(&cached.image);
+            }
+            self.evicted.push(oldest.0);
+        }
+    }
+
+    fn remove_uri(&mut self, uri: &str) {
+        let bytes = &mut self.bytes;
+        self.images.retain(|(cached_uri, _), cached| {
+            let keep = cached_uri != uri;
+            if !keep {
+                *bytes -= image_bytes(&cached.image);
+            }
+            keep
+        });
+    }
+}
+
+pub(crate) struct GrayImageLoader {
+    options: ImageLoaderOptions,
+    cache: Mutex<Cache>,
+}
+
+impl GrayImageLoader {
+    fn new(options: ImageLoaderOptions) -> Self {
+        Self {
+            options,
+            cache: Default::default(),
+        }
+    }
+
+    fn decode(&self, bytes: &[u8], size_hint: SizeHint) -> Result<ColorImage, String> {
+        let image = image::load_from_memory(bytes).map_err(|err| err.to_string())?;
+
+        // Shrink before dithering, the pattern wouldn't survive scaling afterwards
+        let (width, height) = fit_size(image.width(), image.height(), size_hint);
+        let image = if width < image.width() || height < image.height() {
+            image.resize_exact(width, height, FilterType::Triangle)
+        } else {
+            image
+        };
+
+        let image = image.to_luma_alpha8();
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let gamma = 1.0 / self.options.gamma;
+        let gray: Vec<u8> = image
+            .pixels()
+            .map(|pixel| ((pixel[0] as f32 / 255.0).powf(gamma) * 255.0).round() as u8)
+            .collect();
+        let mut levels = vec![0; gray.len()];
+        dither::quantize(&gray, &mut levels, width, [0, 0, width, height], self.options.dithering);
+
+        let pixels = levels
+            .iter()
+            .zip(image.pixels())
+            .map(|(level, pixel)| Color32::from_rgba_unmultiplied(*level, *level, *level, pixel[1]))
+            .collect();
+        Ok(ColorImage {
+            size: [width, height],
+            pixels,
+        })
+    }
+}
+
+impl ImageLoader for GrayImageLoader {
+    fn id(&self) -> &str {
+        IMAGE_LOADER_ID
+    }
+
+    fn load(&self, ctx: &Context, uri: &str, size_hint: SizeHint) -> ImageLoadResult {
+        let key = (uri.to_owned(), size_hint);
+        {
+            let mut cache = self.cache.lock().unwrap();
+            let frame = cache.frame;
+            if let Some(cached) = cache.images.get_mut(&key) {
+                cached.last_used = frame;
+                return Ok(ImagePoll::Ready {
+                    image: cached.image.clone(),
+                });
+            }
+        }
+
+        let bytes = match ctx.try_load_bytes(uri)? {
+            BytesPoll::Ready { bytes, .. } => bytes,
+            BytesPoll::Pending { size } => return Ok(ImagePoll::Pending { size }),
+        };
+        match image::guess_format(&bytes) {
+            Ok(ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Bmp) => {}
+            _ => return Err(LoadError::NotSupported),
+        }
+
+        debug!("Decoding image {}", uri);
+        let image = Arc::new(self.decode(&bytes, size_hint).map_err(LoadError::Loading)?);
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(key, image.clone(), self.options.memory_budget);
+        Ok(ImagePoll::Ready { image })
+    }
+
+    fn forget(&self, uri: &str) {
+        self.cache.lock().unwrap().remove_uri(uri);
+    }
+
+    fn forget_all(&self) {
+        let mut cache = self.cache.lock().unwrap();
+        cache.images.clear();
+        cache.bytes = 0;
+    }
+
+    fn end_frame(&self, frame_index: usize) {
+        self.cache.lock().unwrap().frame = frame_index;
+    }
+
+    fn byte_size(&self) -> usize {
+        self.cache.lock().unwrap().bytes
+    }
+}
+
+fn image_bytes(image: &ColorImage) -> usize {
+    image.pixels.len() * std::mem::size_of::<Color32>()
+}
+
+/// The size an image should be decoded at, keeping its aspect ratio and never upscaling
+fn fit_size(width: u32, height: u32, size_hint: SizeHint) -> (u32, u32) {
+    let (w, h) = (width as f32, height as f32);
+    let scale = match size_hint {
+        SizeHint::Scale(scale) => scale.into_inner(),
+        SizeHint::Width(target) => target as f32 / w,
+        SizeHint::Height(target) => target as f32 / h,
+        SizeHint::Size(target_w, target_h) => (target_w as f32 / w).min(target_h as f32 / h),
+    }
+    .min(1.0);
+    (
+        ((w * scale).round() as u32).max(1),
+        ((h * scale).round() as u32).max(1),
+    )
+}
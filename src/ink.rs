@@ -0,0 +1,194 @@
+use std::os::raw::c_int;
+use std::sync::{Arc, Mutex};
+
+use egui::{Context, Id, Pos2, Rect, Vec2};
+use fbink_sys::{
+    fbink_fill_rect_rgba, fbink_refresh_rect, FBInkConfig, FBInkRect, WFM_MODE_INDEX_E_WFM_A2,
+    WFM_MODE_INDEX_T,
+};
+use log::{debug, warn};
+
+use crate::fbink::FBINK_LOCK;
+use crate::pen::PenSample;
+use crate::rotation::TouchTransform;
+
+/// A stroke drawn in an ink region, from the pen going down to it going up
+#[derive(Clone, Debug, PartialEq)]
+pub struct InkStroke {
+    /// Positions are in points
+    pub samples: Vec<PenSample>,
+    /// In points, as given to `ink_region`
+    pub width: f32,
+    /// Drawn with the eraser end, it was inked in white
+    pub eraser: bool,
+}
+
+fn regions_id() -> Id {
+    Id::new("egui_fbink_ink_regions")
+}
+
+fn strokes_id() -> Id {
+    Id::new("egui_fbink_ink_strokes")
+}
+
+/// Lets the stylus draw straight to the screen inside `rect`, `width` points wide, without
+/// waiting for egui. For this frame only, call it every frame the region is shown
+pub fn ink_region(ctx: &Context, rect: Rect, width: f32) {
+    ctx.data_mut(|data| {
+        data.get_temp_mut_or_default::<Vec<(Rect, f32)>>(regions_id())
+            .push((rect, width))
+    });
+}
+
+/// The strokes finished since the last frame. The app has to draw them from now on:
+/// what was inked is replaced by what this frame draws there
+pub fn ink_strokes(ctx: &Context) -> Vec<InkStroke> {
+    ctx.data(|data| data.get_temp(strokes_id()).unwrap_or_default())
+}
+
+/// The regions requested during the last frame, in points
+pub(crate) fn take_regions(ctx: &Context) -> Vec<(Rect, f32)> {
+    ctx.data_mut(|data| data.remove_temp(regions_id()).unwrap_or_default())
+}
+
+pub(crate) fn store_strokes(ctx: &Context, strokes: Vec<InkStroke>) {
+    ctx.data_mut(|data| data.insert_temp(strokes_id(), strokes));
+}
+
+/// Draws the stylus in the pen thread, as soon as it moves. Everything here is in
+/// framebuffer pixels, before rotation
+pub(crate) struct Ink {
+    fd: c_int,
+    cfg: FBInkConfig,
+    /// Follows the framebuffer when its rotation or size changes
+    transform: TouchTransform,
+    /// Night mode done in software, white ink shows as black
+    inverted: bool,
+    regions: Vec<(Rect, f32)>,
+    /// The stroke being drawn, and the region it is clipped to
+    stroke: Option<(InkStroke, Rect)>,
+    finished: Vec<InkStroke>,
+}
+
+pub(crate) type SharedInk = Arc<Mutex<Ink>>;
+
+impl Ink {
+    pub fn new(fd: c_int, cfg: FBInkConfig, transform: TouchTransform) -> Self {
+        Self {
+            fd,
+            cfg,
+            transform,
+            inverted: false,
+            regions: Vec::new(),
+            stroke: None,
+            finished: Vec::new(),
+        }
+    }
+
+    /// Follows the runner's settings and the regions of the last frame
+    pub fn update(
+        &mut self,
+        cfg: FBInkConfig,
+        transform: TouchTransform,
+        inverted: bool,
+        regions: Vec<(Rect, f32)>,
+    ) {
+        self.cfg = cfg;
+        self.transform = transform;
+        self.inverted = inverted;
+        self.regions = regions;
+    }
+
+    /// Where a stylus position, as the pen thread reads it, is in the framebuffer
+    pub fn to_framebuffer(&self, pos: Pos2) -> Pos2 {
+        self.transform.to_framebuffer(pos)
+    }
+
+    pub fn take_strokes(&mut self) -> Vec<InkStroke> {
+        std::mem::take(&mut self.finished)
+    }
+
+    pub fn has_finished(&self) -> bool {
+        !self.finished.is_empty()
+    }
+
+    /// Inks `sample` if it's part of a stroke. Returns whether it was, egui doesn't get it then
+    pub fn handle(&mut self, sample: &PenSample) -> bool {
+        if let Some((stroke, region)) = &mut self.stroke {
+            if !sample.touching {
+                debug!("Inked a stroke of {} samples", stroke.samples.len());
+                let (stroke, _) = self.stroke.take().unwrap();
+                self.finished.push(stroke);
+                return true;
+            }
+            let from = stroke.samples.last().map_or(sample.pos, |last| last.pos);
+            stroke.samples.push(*sample);
+            let (width, eraser, region) = (stroke.width, stroke.eraser, *region);
+            self.draw(from, sample.pos, width, eraser, region);
+            return true;
+        }
+        if !sample.touching {
+            return false;
+        }
+        let Some(&(region, width)) = self
+            .regions
+            .iter()
+            .find(|(region, _)| region.contains(sample.pos))
+        else {
+            return false;
+        };
+        let stroke = InkStroke {
+            samples: vec![*sample],
+            width,
+            eraser: sample.eraser,
+        };
+        self.stroke = Some((stroke, region));
+        self.draw(sample.pos, sample.pos, width, sample.eraser, region);
+        true
+    }
+
+    /// Stamps squares along the segment, then refreshes it with A2: black and white only,
+    /// but fast enough to follow a pen
+    fn draw(&self, from: Pos2, to: Pos2, width: f32, eraser: bool, region: Rect) {
+        let gray = if eraser != self.inverted { 255 } else { 0 };
+        let half = (width / 2.0).max(0.5);
+        let steps = (from.distance(to) / half.max(1.0)).ceil().max(1.0) as usize;
+
+        let _lock = FBINK_LOCK.lock().unwrap();
+        let mut fill_cfg = self.cfg;
+        fill_cfg.no_refresh = true;
+        for step in 0..=steps {
+            let center = from.lerp(to, step as f32 / steps as f32);
+            let stamp = Rect::from_center_size(center, Vec2::splat(half * 2.0)).intersect(region);
+            if let Some(rect) = fbink_rect(stamp) {
+                unsafe {
+                    fbink_fill_rect_rgba(self.fd, &fill_cfg, &rect, false, gray, gray, gray, 255)
+                };
+            }
+        }
+
+        let mut refresh_cfg = self.cfg;
+        refresh_cfg.is_flashing = false;
+        refresh_cfg.wfm_mode = WFM_MODE_INDEX_E_WFM_A2 as WFM_MODE_INDEX_T;
+        let area = Rect::from_two_pos(from, to)
+            .expand(half + 1.0)
+            .intersect(region);
+        if let Some(rect) = fbink_rect(area) {
+            if unsafe { fbink_refresh_rect(self.fd, &rect, &refresh_cfg) } < 0 {
+                warn!("Failed to refresh ink at {:?}", area);
+            }
+        }
+    }
+}
+
+fn fbink_rect(area: Rect) -> Option<FBInkRect> {
+    if !area.is_positive() {
+        return None;
+    }
+    let mut rect: FBInkRect = unsafe { std::mem::zeroed() };
+    rect.left = area.min.x.floor().max(0.0) as u16;
+    rect.top = area.min.y.floor().max(0.0) as u16;
+    rect.width = (area.max.x.ceil() - area.min.x.floor()).max(1.0) as u16;
+    rect.height = (area.max.y.ceil() - area.min.y.floor()).max(1.0) as u16;
+    Some(rect)
+}
@@ -0,0 +1,193 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use libc::{input_absinfo, input_event};
+use log::debug;
+
+// From linux/input-event-codes.h
+pub const EV_SYN: u16 = 0x00;
+pub const EV_KEY: u16 = 0x01;
+pub const EV_ABS: u16 = 0x03;
+pub const EV_MSC: u16 = 0x04;
+pub const SYN_REPORT: u16 = 0x00;
+pub const KEY_POWER: u16 = 0x74;
+pub const BTN_TOOL_PEN: u16 = 0x140;
+pub const BTN_TOOL_RUBBER: u16 = 0x141;
+pub const BTN_TOUCH: u16 = 0x14a;
+pub const BTN_STYLUS: u16 = 0x14b;
+pub const ABS_X: u16 = 0x00;
+pub const ABS_Y: u16 = 0x01;
+pub const ABS_PRESSURE: u16 = 0x18;
+pub const ABS_TILT_X: u16 = 0x1a;
+pub const ABS_TILT_Y: u16 = 0x1b;
+pub const ABS_MT_SLOT: u16 = 0x2f;
+pub const ABS_MT_POSITION_X: u16 = 0x35;
+pub const ABS_MT_POSITION_Y: u16 = 0x36;
+pub const ABS_MT_TRACKING_ID: u16 = 0x39;
+pub const MSC_RAW: u16 = 0x03;
+
+/// A `/dev/input/event*` device, read without going through libevdev
+pub struct InputDevice {
+    file: File,
+    path: PathBuf,
+}
+
+impl InputDevice {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK | libc::O_CLOEXEC)
+            .open(path)?;
+        debug!("Opened input device {}", path.display());
+        Ok(Self {
+            file,
+            path: path.to_owned(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Waits until there is something to read, or `timeout` passes. `None` waits forever
+    pub fn wait(&self, timeout: Option<Duration>) -> io::Result<bool> {
+        let mut fd = libc::pollfd {
+            fd: self.file.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout = timeout.map_or(-1, |t| t.as_millis().min(i32::MAX as u128) as i32);
+        match unsafe { libc::poll(&mut fd, 1, timeout) } {
+            -1 => Err(io::Error::last_os_error()),
+            0 => Ok(false),
+            _ => Ok(true),
+        }
+    }
+
+    /// Range of an absolute axis, like `ABS_MT_POSITION_X`
+    pub fn abs_info(&self, code: u16) -> io::Result<input_absinfo> {
+        // EVIOCGABS(code), that is _IOR('E', 0x40 + code, struct input_absinfo)
+        let request = (2 << 30)
+            | (std::mem::size_of::<input_absinfo>() << 16)
+            | ((b'E' as usize) << 8)
+            | (0x40 + code as usize);
+        let mut info: input_absinfo = unsafe { std::mem::zeroed() };
+        if unsafe { libc::ioctl(self.file.as_raw_fd(), request as _, &mut info) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(info)
+    }
+
+    /// Everything the kernel has queued up so far
+    pub fn read_events(&mut self) -> io::Result<Vec<input_event>> {
+        const SIZE: usize = std::mem::size_of::<input_event>();
+        let mut buffer = [0u8; SIZE * 64];
+        let mut events = Vec::new();
+        loop {
+            let read = match self.file.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            // The kernel only ever hands out whole events
+            for chunk in buffer[..read].chunks_exact(SIZE) {
+                events.push(unsafe { std::ptr::read_unaligned(chunk.as_ptr() as *const input_event) });
+            }
+        }
+        Ok(events)
+    }
+}
+
+/// Scales raw axis values to `size`, from the range the device reports
+#[derive(Clone, Copy)]
+pub struct Axis {
+    min: f32,
+    range: f32,
+    size: f32,
+}
+
+impl Axis {
+    /// The range of the first of `codes` the device has
+    pub fn new(device: &InputDevice, codes: &[u16], size: f32) -> Option<Self> {
+        let info = codes.iter().find_map(|code| device.abs_info(*code).ok())?;
+        (info.maximum > info.minimum).then(|| Axis {
+            min: info.minimum as f32,
+            range: (info.maximum - info.minimum + 1) as f32,
+            size,
+        })
+    }
+
+    /// For devices that don't report a range: raw values are taken as pixels of an axis
+    /// `pixels` long, and scaled to 0 to 1
+    pub fn assumed(pixels: f32) -> Self {
+        Axis {
+            min: 0.0,
+            range: pixels,
+            size: 1.0,
+        }
+    }
+
+    /// The raw value if the range is unknown
+    pub fn scale(axis: Option<Axis>, value: i32) -> f32 {
+        match axis {
+            Some(axis) => (value as f32 - axis.min) * axis.size / axis.range,
+            None => value as f32,
+        }
+    }
+}
+
+/// The first event device whose capabilities of kind `kind` ("msc", "abs", "key"...) have
+/// bit `code` set, as listed in `/sys/class/input`
+pub fn find_device(kind: &str, code: u16) -> Option<PathBuf> {
+    find_devices(kind, code).into_iter().next()
+}
+
+/// All the event devices `find_device` could pick, in order
+pub fn find_devices(kind: &str, code: u16) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir("/sys/class/input") else {
+        return Vec::new();
+    };
+    let mut entries: Vec<_> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| name.starts_with("event"))
+        .collect();
+    entries.sort();
+    entries
+        .into_iter()
+        .map(|name| PathBuf::from(format!("/dev/input/{}", name)))
+        .filter(|path| device_has(path, kind, code))
+        .collect()
+}
+
+/// Whether the event device at `path` has capability `code` of kind `kind`
+pub fn device_has(path: &Path, kind: &str, code: u16) -> bool {
+    let Some(name) = path.file_name() else {
+        return false;
+    };
+    fs::read_to_string(
+        Path::new("/sys/class/input")
+            .join(name)
+            .join("device/capabilities")
+            .join(kind),
+    )
+    .is_ok_and(|capabilities| has_capability(&capabilities, code))
+}
+
+/// The kernel prints the bitmask as space separated hex words, the most significant first
+fn has_capability(capabilities: &str, code: u16) -> bool {
+    let bits = usize::BITS as usize;
+    let word = code as usize / bits;
+    capabilities
+        .split_whitespace()
+        .rev()
+        .nth(word)
+        .and_then(|w| usize::from_str_radix(w, 16).ok())
+        .is_some_and(|w| w & (1 << (code as usize % bits)) != 0)
+}
@@ -0,0 +1,416 @@
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::PathBuf;
+
+use egui::{
+    Align2, Color32, Context, Event, FontId, Id, Key, LayerId, Modifiers, Order, Pos2, Rect,
+    Rounding, Stroke, TouchPhase, Vec2,
+};
+use log::{debug, warn};
+
+use crate::clipboard::Clipboard;
+
+const BUILTIN_LAYOUTS: [(&str, &str); 4] = [
+    ("qwerty", include_str!("../layouts/qwerty.txt")),
+    ("azerty", include_str!("../layouts/azerty.txt")),
+    ("numeric", include_str!("../layouts/numeric.txt")),
+    ("symbols", include_str!("../layouts/symbols.txt")),
+];
+
+#[derive(Clone, Debug)]
+pub struct KeyboardOptions {
+    /// Layout shown when the keyboard comes up, by name
+    pub layout: String,
+    /// More layouts, `<name>.txt` files in the format of the ones in `layouts/`.
+    /// They replace the built-in ones with the same name
+    pub layouts_dir: Option<PathBuf>,
+    /// Height of a row of keys, in millimeters
+    pub key_height_mm: f32,
+}
+
+impl Default for KeyboardOptions {
+    fn default() -> Self {
+        Self {
+            layout: "qwerty".to_owned(),
+            layouts_dir: None,
+            key_height_mm: 9.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Action {
+    Text(String),
+    Key(Key),
+    Shift,
+    Layout(String),
+    Letters,
+    Hide,
+    Cut,
+    Copy,
+    Paste,
+}
+
+#[derive(Clone, Debug)]
+struct KeyDef {
+    action: Action,
+    width: f32,
+}
+
+type Layout = Vec<Vec<KeyDef>>;
+
+/// Keys egui gets while typing, drawn by the runner under the app.
+/// It isn't made of egui widgets: tapping one would take the focus away from the text edit
+pub(crate) struct Keyboard {
+    options: KeyboardOptions,
+    layouts: HashMap<String, Layout>,
+    layout: String,
+    shift: bool,
+    visible: bool,
+    /// Where the keyboard was last drawn, in points
+    area: Rect,
+    /// The focused text edit, in points
+    field: Option<Rect>,
+    /// Chosen when the keyboard comes up, so it doesn't jump around while typing
+    at_top: Option<bool>,
+    /// The finger on the keyboard, and the key under it
+    touch: Option<(u64, Option<(usize, usize)>)>,
+}
+
+impl Keyboard {
+    pub fn new(mut options: KeyboardOptions) -> Self {
+        let mut layouts = HashMap::new();
+        for (name, text) in BUILTIN_LAYOUTS {
+            layouts.insert(name.to_owned(), parse_layout(text));
+        }
+        if let Some(dir) = &options.layouts_dir {
+            match fs::read_dir(dir) {
+                Ok(entries) => {
+                    for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+                        if path.extension() != Some(OsStr::new("txt")) {
+                            continue;
+                        }
+                        let Some(name) = path.file_stem().map(|n| n.to_string_lossy().into_owned())
+                        else {
+                            continue;
+                        };
+                        match fs::read_to_string(&path) {
+                            Ok(text) => {
+                                debug!("Loaded keyboard layout {}", name);
+                                layouts.insert(name, parse_layout(&text));
+                            }
+                            Err(err) => warn!("Failed to read {}: {}", path.display(), err),
+                        }
+                    }
+                }
+                Err(err) => warn!("Failed to read layouts from {}: {}", dir.display(), err),
+            }
+        }
+        // Coming back to it later has to find it too
+        if !layouts.contains_key(&options.layout) {
+            warn!("There is no keyboard layout {}, using qwerty", options.layout);
+            options.layout = "qwerty".to_owned();
+        }
+        Self {
+            layout: options.layout.clone(),
+            options,
+            layouts,
+            shift: false,
+            visible: false,
+            area: Rect::NOTHING,
+            field: None,
+            at_top: None,
+            touch: None,
+        }
+    }
+
+    /// Returns whether it changed
+    pub fn set_visible(&mut self, visible: bool) -> bool {
+        if self.visible == visible {
+            return false;
+        }
+        debug!("{} the keyboard", if visible { "Showing" } else { "Hiding" });
+        self.visible = visible;
+        self.touch = None;
+        self.at_top = None;
+        self.shift = false;
+        self.layout = self.options.layout.clone();
+        true
+    }
+
+    fn rows(&self) -> &Layout {
+        &self.layouts[&self.layout]
+    }
+
+    /// Height in points, for a panel of `dpi` drawn at `pixels_per_point`
+    pub fn height(&self, dpi: f32, pixels_per_point: f32) -> f32 {
+        if !self.visible {
+            return 0.0;
+        }
+        let dpi = if dpi > 0.0 { dpi } else { 160.0 };
+        let row = self.options.key_height_mm / 25.4 * dpi / pixels_per_point;
+        row * self.rows().len() as f32
+    }
+
+    /// Where the text edit that has the focus is, from egui's IME output
+    pub fn set_field(&mut self, field: Option<Rect>) {
+        self.field = field;
+    }
+
+    /// Lays the keyboard out at the bottom of `screen`, which is in points, or at the top
+    /// if it would cover the text edit. Returns what is left for the app
+    pub fn set_area(&mut self, screen: Rect, height: f32) -> Rect {
+        let field = self.field;
+        let at_top = *self
+            .at_top
+            .get_or_insert_with(|| field.is_some_and(|field| field.max.y > screen.max.y - height));
+        let mut app = screen;
+        if at_top {
+            self.area = Rect::from_min_max(screen.min, Pos2::new(screen.max.x, screen.min.y + height));
+            app.min.y += height;
+        } else {
+            self.area = Rect::from_min_max(Pos2::new(screen.min.x, screen.max.y - height), screen.max);
+            app.max.y -= height;
+        }
+        app
+    }
+
+    /// Where it is drawn, in points
+    pub fn area(&self) -> Rect {
+        self.area
+    }
+
+    pub fn contains(&self, pos: Pos2) -> bool {
+        self.visible && self.area.contains(pos)
+    }
+
+    pub fn is_touched_by(&self, id: u64) -> bool {
+        self.touch.is_some_and(|(touch, _)| touch == id)
+    }
+
+    fn key_rects(&self) -> Vec<(usize, usize, Rect)> {
+        let rows = self.rows();
+        let row_height = self.area.height() / rows.len().max(1) as f32;
+        let mut rects = Vec::new();
+        for (r, row) in rows.iter().enumerate() {
+            let total: f32 = row.iter().map(|key| key.width).sum();
+            let unit = self.area.width() / total.max(1.0);
+            let mut x = self.area.min.x;
+            let y = self.area.min.y + r as f32 * row_height;
+            for (k, key) in row.iter().enumerate() {
+                let rect = Rect::from_min_size(Pos2::new(x, y), Vec2::new(key.width * unit, row_height));
+                rects.push((r, k, rect));
+                x += key.width * unit;
+            }
+        }
+        rects
+    }
+
+    fn key_at(&self, pos: Pos2) -> Option<(usize, usize)> {
+        self.key_rects()
+            .into_iter()
+            .find(|(_, _, rect)| rect.contains(pos))
+            .map(|(r, k, _)| (r, k))
+    }
+
+    /// Follows a finger on the keyboard. Keys are typed when it goes up, so sliding off
+    /// a key cancels it
+    pub fn touch(
+        &mut self,
+        id: u64,
+        phase: TouchPhase,
+        pos: Pos2,
+        clipboard: &mut Clipboard,
+    ) -> Vec<Event> {
+        match phase {
+            TouchPhase::Start => {
+                self.touch = Some((id, self.key_at(pos)));
+                Vec::new()
+            }
+            TouchPhase::Move => {
+                self.touch = Some((id, self.key_at(pos)));
+                Vec::new()
+            }
+            TouchPhase::End => {
+                let key = self.key_at(pos);
+                let pressed = self.touch.take().and_then(|(_, key)| key);
+                match key.filter(|key| Some(*key) == pressed) {
+                    Some((r, k)) => {
+                        let action = self.rows()[r][k].action.clone();
+                        self.press(action, clipboard)
+                    }
+                    None => Vec::new(),
+                }
+            }
+            TouchPhase::Cancel => {
+                self.touch = None;
+                Vec::new()
+            }
+        }
+    }
+
+    fn press(&mut self, action: Action, clipboard: &mut Clipboard) -> Vec<Event> {
+        let key = |key| {
+            [true, false]
+                .map(|pressed| Event::Key {
+                    key,
+                    physical_key: None,
+                    pressed,
+                    repeat: false,
+                    modifiers: Modifiers::NONE,
+                })
+                .to_vec()
+        };
+        match action {
+            Action::Text(text) => {
+                let text = if self.shift {
+                    self.shift = false;
+                    text.to_uppercase()
+                } else {
+                    text
+                };
+                vec![Event::Text(text)]
+            }
+            Action::Key(k) => key(k),
+            Action::Shift => {
+                self.shift = !self.shift;
+                Vec::new()
+            }
+            Action::Layout(name) => {
+                if self.layouts.contains_key(&name) {
+                    self.layout = name;
+                } else {
+                    warn!("There is no keyboard layout {}", name);
+                }
+                Vec::new()
+            }
+            Action::Letters => {
+                self.layout = self.options.layout.clone();
+                Vec::new()
+            }
+            // egui drops the focus on escape, which hides the keyboard
+            Action::Hide => key(Key::Escape),
+            Action::Cut => vec![Event::Cut],
+            Action::Copy => vec![Event::Copy],
+            Action::Paste => vec![Event::Paste(clipboard.get())],
+        }
+    }
+
+    /// Paints it in plain black and white, so the fast waveform shows it well. Called
+    /// within the frame, so the glyphs of the labels make it into the font texture
+    pub fn paint(&self, ctx: &Context) {
+        // Not laid out yet when it just came up, the next frame draws it
+        if !self.visible || !self.area.is_positive() {
+            return;
+        }
+        let visuals = ctx.style().visuals.clone();
+        let (bg, fg) = if visuals.dark_mode {
+            (Color32::BLACK, Color32::WHITE)
+        } else {
+            (Color32::WHITE, Color32::BLACK)
+        };
+        let key_rects = self.key_rects();
+        let row_height = key_rects.first().map_or(0.0, |(_, _, rect)| rect.height());
+        let font_id = FontId::proportional(row_height * 0.4);
+        let pressed = self.touch.and_then(|(_, key)| key);
+
+        // The keyboard is outside of the app's screen rect, which layer painters clip to
+        let mut painter = ctx.layer_painter(LayerId::new(
+            Order::Foreground,
+            Id::new("egui_fbink_keyboard"),
+        ));
+        painter.set_clip_rect(self.area);
+
+        // The edge next to the app
+        let edge = if self.at_top == Some(true) {
+            [self.area.left_bottom(), self.area.right_bottom()]
+        } else {
+            [self.area.left_top(), self.area.right_top()]
+        };
+        painter.rect_filled(self.area, Rounding::ZERO, bg);
+        painter.line_segment(edge, Stroke::new(2.0, fg));
+        for (r, k, rect) in key_rects {
+            let key = &self.rows()[r][k];
+            let rect = rect.shrink(3.0);
+            let (key_bg, key_fg) =
+                if pressed == Some((r, k)) || (key.action == Action::Shift && self.shift) {
+                    (fg, bg)
+                } else {
+                    (bg, fg)
+                };
+            painter.rect_filled(rect, Rounding::same(4.0), key_bg);
+            painter.rect_stroke(rect, Rounding::same(4.0), Stroke::new(1.5, fg));
+            painter.text(
+                rect.center(),
+                Align2::CENTER_CENTER,
+                self.label(&key.action),
+                font_id.clone(),
+                key_fg,
+            );
+        }
+    }
+
+    fn label(&self, action: &Action) -> String {
+        match action {
+            Action::Text(text) if text == " " => String::new(),
+            Action::Text(text) if self.shift => text.to_uppercase(),
+            Action::Text(text) => text.clone(),
+            Action::Key(Key::Backspace) => "Del".to_owned(),
+            Action::Key(Key::Enter) => "Enter".to_owned(),
+            Action::Key(Key::ArrowLeft) => "<".to_owned(),
+            Action::Key(Key::ArrowRight) => ">".to_owned(),
+            Action::Key(key) => key.name().to_owned(),
+            Action::Shift => "Shift".to_owned(),
+            Action::Layout(name) if name == "numeric" => "123".to_owned(),
+            Action::Layout(name) if name == "symbols" => "#+=".to_owned(),
+            Action::Layout(name) => name.clone(),
+            Action::Letters => "ABC".to_owned(),
+            Action::Hide => "Hide".to_owned(),
+            Action::Cut => "Cut".to_owned(),
+            Action::Copy => "Copy".to_owned(),
+            Action::Paste => "Paste".to_owned(),
+        }
+    }
+}
+
+fn parse_layout(text: &str) -> Layout {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.split_whitespace().map(parse_key).collect())
+        .collect()
+}
+
+fn parse_key(token: &str) -> KeyDef {
+    // "*" alone is a key, "{space}*4" is a key four times as wide
+    let (name, width) = match token.rsplit_once('*') {
+        Some((name, width)) if !name.is_empty() => match width.parse() {
+            Ok(width) => (name, width),
+            Err(_) => (token, 1.0),
+        },
+        _ => (token, 1.0),
+    };
+    let action = match name.strip_prefix('{').and_then(|n| n.strip_suffix('}')) {
+        Some("shift") => Action::Shift,
+        Some("backspace") => Action::Key(Key::Backspace),
+        Some("enter") => Action::Key(Key::Enter),
+        Some("space") => Action::Text(" ".to_owned()),
+        Some("left") => Action::Key(Key::ArrowLeft),
+        Some("right") => Action::Key(Key::ArrowRight),
+        Some("hide") => Action::Hide,
+        Some("letters") => Action::Letters,
+        Some("cut") => Action::Cut,
+        Some("copy") => Action::Copy,
+        Some("paste") => Action::Paste,
+        Some(special) => match special.strip_prefix("layout:") {
+            Some(layout) => Action::Layout(layout.to_owned()),
+            None => {
+                warn!("Unknown keyboard key {{{}}}", special);
+                Action::Text(String::new())
+            }
+        },
+        None => Action::Text(name.to_owned()),
+    };
+    KeyDef { action, width }
+}
@@ -1,26 +1,179 @@
-use crate::backend::{AppRunner};
+use crate::backend::{AppRunner, Inputs};
+use crate::clipboard::Clipboard;
+use crate::fbink::FBInkBackend;
+use crate::gesture::GestureRecognizer;
+use crate::ink::{Ink, SharedInk};
+use crate::keyboard::Keyboard;
 use eframe::{App, NativeOptions};
 use ::egui::Response;
 use egui::{EguiStuff};
 use log::debug;
-use std::{sync::{Arc, Mutex}, thread::sleep, time::Duration};
+use std::path::PathBuf;
+use std::{sync::{Arc, Mutex}, time::Duration};
 
-use crate::fbink::FBInkBackend;
+pub use crate::auto_rotate::{lock_orientation, set_rotation_veto, AutoRotateOptions};
+pub use crate::clipboard::paste;
+pub use crate::device::{power_status, Battery, ChargeState, Device, PowerStatus};
+pub use crate::dither::{dither_region, set_dithering, Dithering};
+pub use crate::dump::{dump, dump_shadow};
+pub use crate::frontlight::{
+    frontlight, FakeFrontlight, Frontlight, FrontlightBackend, NtxFrontlight, SysfsFrontlight,
+};
+pub use crate::gesture::{gestures, Gesture, GestureOptions, SwipeDirection};
+pub use crate::image_loader::{install_image_loaders, ImageLoaderOptions};
+pub use crate::ink::{ink_region, ink_strokes, InkStroke};
+pub use crate::keyboard::KeyboardOptions;
+pub use crate::night_mode::{night_mode, set_night_mode, NightMode};
+pub use crate::pen::{pen, pen_samples, PenSample};
+pub use crate::platform::{output_events, set_url_handler};
+pub use crate::rotation::{rotation, set_rotation, Rotation, TouchTransform};
+pub use crate::suspend::{resume, sleeping, suspend, SuspendOptions};
+pub use crate::theme::{set_theme, theme, Theme};
+pub use crate::touch_profile::{set_touch_profile, TouchProfile};
+pub use crate::zoom::{set_zoom, zoom};
 
+mod auto_rotate;
 mod backend;
+mod clipboard;
+mod color;
+mod device;
+mod dither;
+mod dump;
 mod fbink;
+mod frontlight;
+mod gesture;
+mod image_loader;
+mod ink;
+mod input;
+mod keyboard;
+mod night_mode;
+mod pen;
+mod platform;
 mod egui;
 mod eink_theme;
+mod raster;
+mod rotation;
+mod suspend;
+mod textures;
+mod theme;
+mod touch;
+mod touch_profile;
+mod zoom;
+
+/// How the screen is set up before the first frame
+#[derive(Clone, Debug)]
+pub struct RunnerOptions {
+    pub pixel_per_point: f32,
+    pub zoom_factor: f32,
+    /// Let egui do the zooming, so it scales everything it draws, images and custom
+    /// painting included. Otherwise only the style is scaled
+    pub native_zoom: bool,
+    /// Can be changed later with `set_rotation`
+    pub rotation: Rotation,
+    /// Grows widgets to finger size, `None` keeps the theme's sizes
+    pub touch_profile: Option<TouchProfile>,
+    /// The touch panel's input device. Found through its capabilities if not set
+    pub touch_device: Option<PathBuf>,
+    /// The stylus digitizer, on devices that have one. Found through its capabilities if
+    /// not set
+    pub pen_device: Option<PathBuf>,
+    /// Thresholds for long presses, swipes and pinches
+    pub gestures: GestureOptions,
+    /// Keeps the clipboard in this file, so text copied in one app can be pasted in another
+    /// using the same file. Only kept in memory if not set
+    pub clipboard_file: Option<PathBuf>,
+    /// Launched with the url as its argument when the app opens a link, if the app didn't
+    /// set a handler with `set_url_handler`
+    pub browser: Option<PathBuf>,
+    /// Shown while a text edit has the focus
+    pub keyboard: Option<KeyboardOptions>,
+    /// Where sysfs is, for the power status and the frontlight
+    pub sysfs_root: PathBuf,
+    /// Follow the orientation sensor on devices that have one
+    pub auto_rotate: Option<AutoRotateOptions>,
+    /// Sleep and wake with the power button, `None` leaves it to the system
+    pub suspend: Option<SuspendOptions>,
+}
+
+impl Default for RunnerOptions {
+    fn default() -> Self {
+        Self {
+            pixel_per_point: 1.0,
+            zoom_factor: 1.0,
+            native_zoom: true,
+            rotation: Rotation::Deg0,
+            touch_profile: Some(TouchProfile::default()),
+            touch_device: None,
+            pen_device: None,
+            gestures: GestureOptions::default(),
+            clipboard_file: None,
+            browser: None,
+            keyboard: Some(KeyboardOptions::default()),
+            sysfs_root: PathBuf::from("/sys"),
+            auto_rotate: None,
+            suspend: Some(SuspendOptions::default()),
+        }
+    }
+}
+
+pub fn start(app: Box<dyn App>, _native_options: NativeOptions, pixel_per_point: f32, zoom_factor: f32) -> () {
+    start_with_options(
+        app,
+        RunnerOptions {
+            pixel_per_point,
+            zoom_factor,
+            ..Default::default()
+        },
+    )
+}
 
-pub fn start(mut app: Box<dyn App>, native_options: NativeOptions, pixel_per_point: f32, zoom_factor: f32) -> () {
+pub fn start_with_options(app: Box<dyn App>, options: RunnerOptions) {
     let mut fb = FBInkBackend::new();
-    let mut egui_stuff: EguiStuff = EguiStuff::new(app, &fb, pixel_per_point, zoom_factor);
-    egui_stuff.manage_zoom();
-    let mut runner = AppRunner::new(egui_stuff, fb);
+    if options.rotation != Rotation::Deg0 {
+        fb.set_rotation(options.rotation);
+    }
+    let mut egui_stuff: EguiStuff = EguiStuff::new(
+        app,
+        &fb,
+        options.pixel_per_point,
+        options.zoom_factor,
+        options.native_zoom,
+        options.touch_profile,
+    );
+    egui_stuff.apply_style();
+    if let Some(auto_rotate) = options.auto_rotate {
+        auto_rotate::spawn(egui_stuff.ctx.clone(), auto_rotate);
+    }
+    if let Some(suspend) = &options.suspend {
+        suspend::spawn(egui_stuff.ctx.clone(), suspend.power_button.clone());
+    }
+    let light = Frontlight::for_device(&fb.device_codename(), &options.sysfs_root);
+    frontlight::install(&egui_stuff.ctx, light);
+    let touches = touch::spawn(egui_stuff.ctx.clone(), options.touch_device, fb.touch);
+    let ink: SharedInk = Arc::new(Mutex::new(Ink::new(fb.fd, fb.cfg, fb.touch)));
+    let pen = pen::spawn(egui_stuff.ctx.clone(), options.pen_device, fb.touch, ink.clone());
+    let ink = pen.is_some().then_some(ink);
+    let keyboard = options.keyboard.map(Keyboard::new);
+    let clipboard = Clipboard::new(options.clipboard_file);
+    let mut runner = AppRunner::new(
+        egui_stuff,
+        fb,
+        Inputs {
+            touches,
+            gestures: GestureRecognizer::new(options.gestures),
+            pen,
+            ink,
+            keyboard,
+        },
+        clipboard,
+        options.browser,
+        Device::new(options.sysfs_root),
+        options.suspend.unwrap_or_default(),
+    );
 
     loop {
         runner.next_frame();
-        sleep(Duration::from_secs(20));
+        runner.wait(Duration::from_secs(20));
     }
 }
 
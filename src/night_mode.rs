@@ -0,0 +1,38 @@
+use egui::{Context, Id};
+
+/// Light on dark rendering, easier on the eyes at night
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NightMode {
+    #[default]
+    Off,
+    /// Inverts everything on the way to the panel, with the EPDC flag when the device has
+    /// one and in software otherwise. Images are inverted too
+    Inverted,
+    /// Swaps the theme to a light on dark variant, images keep their colors
+    DarkTheme,
+}
+
+fn requested_id() -> Id {
+    Id::new("egui_fbink_requested_night_mode")
+}
+
+fn current_id() -> Id {
+    Id::new("egui_fbink_night_mode")
+}
+
+/// Switches night mode on or off, starting with the next frame. The whole screen is flashed
+pub fn set_night_mode(ctx: &Context, night_mode: NightMode) {
+    ctx.data_mut(|data| data.insert_temp(requested_id(), night_mode));
+}
+
+pub fn night_mode(ctx: &Context) -> NightMode {
+    ctx.data(|data| data.get_temp(current_id()).unwrap_or_default())
+}
+
+pub(crate) fn take_request(ctx: &Context) -> Option<NightMode> {
+    ctx.data_mut(|data| data.remove_temp(requested_id()))
+}
+
+pub(crate) fn store_current(ctx: &Context, night_mode: NightMode) {
+    ctx.data_mut(|data| data.insert_temp(current_id(), night_mode));
+}
@@ -0,0 +1,207 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use egui::{Context, Event, Id, Modifiers, PointerButton, Pos2, Vec2};
+use log::{debug, error};
+
+use crate::ink::SharedInk;
+use crate::input::{
+    find_device, Axis, InputDevice, ABS_PRESSURE, ABS_TILT_X, ABS_TILT_Y, ABS_X, ABS_Y, BTN_STYLUS,
+    BTN_TOOL_PEN, BTN_TOOL_RUBBER, BTN_TOUCH, EV_ABS, EV_KEY, EV_SYN, SYN_REPORT,
+};
+use crate::rotation::TouchTransform;
+
+/// What the stylus reported, once per report of the digitizer
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PenSample {
+    /// In points, on the logical screen. From 0 to 1 along each axis of the digitizer until
+    /// the runner hands it over
+    pub pos: Pos2,
+    /// From 0 to 1
+    pub pressure: f32,
+    /// From -1 to 1 on each axis, as the digitizer reports it
+    pub tilt: Vec2,
+    /// Near enough to the screen to be tracked
+    pub in_range: bool,
+    /// On the screen
+    pub touching: bool,
+    /// The eraser end is used
+    pub eraser: bool,
+    /// The button on the side is held
+    pub barrel_button: bool,
+}
+
+/// Samples read by the background thread, waiting for the next frame
+pub(crate) type PenQueue = Arc<Mutex<Vec<PenSample>>>;
+
+fn samples_id() -> Id {
+    Id::new("egui_fbink_pen_samples")
+}
+
+fn current_id() -> Id {
+    Id::new("egui_fbink_pen")
+}
+
+/// Everything the stylus reported since the last frame, for drawing at full resolution
+pub fn pen_samples(ctx: &Context) -> Vec<PenSample> {
+    ctx.data(|data| data.get_temp(samples_id()).unwrap_or_default())
+}
+
+/// Where the stylus is now, `None` when it's away from the screen
+pub fn pen(ctx: &Context) -> Option<PenSample> {
+    ctx.data(|data| data.get_temp::<PenSample>(current_id()))
+        .filter(|sample| sample.in_range)
+}
+
+pub(crate) fn store(ctx: &Context, samples: Vec<PenSample>) {
+    ctx.data_mut(|data| {
+        if let Some(last) = samples.last() {
+            data.insert_temp(current_id(), *last);
+        }
+        data.insert_temp(samples_id(), samples);
+    });
+}
+
+/// Reads the stylus digitizer in a background thread, if there is one. Like for touches,
+/// `transform` is only used if the digitizer doesn't report its range, the rest is applied
+/// when samples are handed to egui. Samples `ink` takes are drawn right away and never
+/// reach egui
+pub(crate) fn spawn(
+    ctx: Context,
+    device: Option<PathBuf>,
+    transform: TouchTransform,
+    ink: SharedInk,
+) -> Option<PenQueue> {
+    let Some(path) = device.or_else(|| find_device("key", BTN_TOOL_PEN)) else {
+        // Most devices don't have one
+        debug!("No stylus found");
+        return None;
+    };
+    let mut device = match InputDevice::open(&path) {
+        Ok(device) => device,
+        Err(err) => {
+            error!("Failed to open stylus {}: {}", path.display(), err);
+            return None;
+        }
+    };
+
+    let [width, height] = transform.size;
+    let (x_size, y_size) = if transform.swap_axes {
+        (height, width)
+    } else {
+        (width, height)
+    };
+    let x_axis = Axis::new(&device, &[ABS_X], 1.0).or(Some(Axis::assumed(x_size)));
+    let y_axis = Axis::new(&device, &[ABS_Y], 1.0).or(Some(Axis::assumed(y_size)));
+    let pressure_axis = Axis::new(&device, &[ABS_PRESSURE], 1.0);
+    let tilt_x_axis = Axis::new(&device, &[ABS_TILT_X], 2.0);
+    let tilt_y_axis = Axis::new(&device, &[ABS_TILT_Y], 2.0);
+
+    let queue = PenQueue::default();
+    let thread_queue = queue.clone();
+    thread::Builder::new()
+        .name("pen".to_owned())
+        .spawn(move || {
+            let mut sample = PenSample::default();
+            let mut reported = sample;
+            loop {
+                if let Err(err) = device.wait(None) {
+                    error!("Failed to wait for {}: {}", device.path().display(), err);
+                    return;
+                }
+                let events = match device.read_events() {
+                    Ok(events) => events,
+                    Err(err) => {
+                        error!("Failed to read {}: {}", device.path().display(), err);
+                        return;
+                    }
+                };
+                for event in events {
+                    match (event.type_, event.code) {
+                        (EV_KEY, BTN_TOOL_PEN) => sample.in_range = event.value != 0,
+                        (EV_KEY, BTN_TOOL_RUBBER) => {
+                            sample.in_range = event.value != 0;
+                            sample.eraser = event.value != 0;
+                        }
+                        (EV_KEY, BTN_TOUCH) => sample.touching = event.value != 0,
+                        (EV_KEY, BTN_STYLUS) => sample.barrel_button = event.value != 0,
+                        (EV_ABS, ABS_X) => sample.pos.x = Axis::scale(x_axis, event.value),
+                        (EV_ABS, ABS_Y) => sample.pos.y = Axis::scale(y_axis, event.value),
+                        (EV_ABS, ABS_PRESSURE) => {
+                            sample.pressure = Axis::scale(pressure_axis, event.value)
+                        }
+                        (EV_ABS, ABS_TILT_X) => {
+                            sample.tilt.x = Axis::scale(tilt_x_axis, event.value) - 1.0
+                        }
+                        (EV_ABS, ABS_TILT_Y) => {
+                            sample.tilt.y = Axis::scale(tilt_y_axis, event.value) - 1.0
+                        }
+                        (EV_SYN, SYN_REPORT) => {
+                            if !sample.in_range {
+                                // Whatever was held is let go with the pen
+                                sample = PenSample::default();
+                            }
+                            if sample == reported {
+                                continue;
+                            }
+                            let mut ink = ink.lock().unwrap();
+                            let mut inked = sample;
+                            inked.pos = ink.to_framebuffer(sample.pos);
+                            if ink.handle(&inked) {
+                                if ink.has_finished() {
+                                    ctx.request_repaint();
+                                }
+                                reported = sample;
+                                continue;
+                            }
+                            drop(ink);
+                            thread_queue.lock().unwrap().push(sample);
+                            ctx.request_repaint();
+                            reported = sample;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        })
+        .expect("Failed to spawn the pen thread");
+
+    debug!("Reading the stylus from {}", path.display());
+    Some(queue)
+}
+
+/// Drives egui's pointer with the stylus: touching the screen is the primary button,
+/// the barrel button a secondary click
+pub(crate) fn pointer_events(last: &PenSample, sample: &PenSample) -> Vec<Event> {
+    let mut events = Vec::new();
+    let button = |button, pos, pressed| Event::PointerButton {
+        pos,
+        button,
+        pressed,
+        modifiers: Modifiers::NONE,
+    };
+    if !sample.in_range {
+        if last.in_range {
+            if last.touching {
+                events.push(button(PointerButton::Primary, last.pos, false));
+            }
+            events.push(Event::PointerGone);
+        }
+        return events;
+    }
+    if sample.pos != last.pos || !last.in_range {
+        events.push(Event::PointerMoved(sample.pos));
+    }
+    if sample.touching != last.touching {
+        events.push(button(PointerButton::Primary, sample.pos, sample.touching));
+    }
+    if sample.barrel_button != last.barrel_button {
+        events.push(button(
+            PointerButton::Secondary,
+            sample.pos,
+            sample.barrel_button,
+        ));
+    }
+    events
+}
@@ -0,0 +1,56 @@
+use std::path::Path;
+use std::process::Command;
+use std::sync::Arc;
+use std::thread;
+
+use egui::output::{OpenUrl, OutputEvent};
+use egui::{Context, Id};
+use log::{debug, warn};
+
+type UrlHandler = Arc<dyn Fn(&OpenUrl) + Send + Sync>;
+
+fn url_handler_id() -> Id {
+    Id::new("egui_fbink_url_handler")
+}
+
+fn events_id() -> Id {
+    Id::new("egui_fbink_output_events")
+}
+
+/// Called with the links the app opens, instead of launching the browser
+pub fn set_url_handler(ctx: &Context, handler: impl Fn(&OpenUrl) + Send + Sync + 'static) {
+    let handler: UrlHandler = Arc::new(handler);
+    ctx.data_mut(|data| data.insert_temp(url_handler_id(), handler));
+}
+
+/// What the widgets reported during the last frame: clicks, focus and value changes
+pub fn output_events(ctx: &Context) -> Vec<OutputEvent> {
+    ctx.data(|data| data.get_temp(events_id()).unwrap_or_default())
+}
+
+pub(crate) fn store_events(ctx: &Context, events: Vec<OutputEvent>) {
+    for event in &events {
+        debug!("{:?}", event);
+    }
+    ctx.data_mut(|data| data.insert_temp(events_id(), events));
+}
+
+/// Hands the url to the app's handler, or to `browser`
+pub(crate) fn open_url(ctx: &Context, open_url: &OpenUrl, browser: Option<&Path>) {
+    if let Some(handler) = ctx.data(|data| data.get_temp::<UrlHandler>(url_handler_id())) {
+        handler(open_url);
+        return;
+    }
+    let Some(browser) = browser else {
+        warn!("No browser to open {}", open_url.url);
+        return;
+    };
+    debug!("Opening {} with {}", open_url.url, browser.display());
+    match Command::new(browser).arg(&open_url.url).spawn() {
+        // Waited for in the background, so it doesn't linger as a zombie
+        Ok(mut child) => {
+            thread::spawn(move || child.wait());
+        }
+        Err(err) => warn!("Failed to launch {}: {}", browser.display(), err),
+    }
+}
@@ -0,0 +1,148 @@
+use egui::epaint::{Mesh, Vertex};
+use egui::{Color32, Pos2, Rect};
+
+use crate::textures::Texture;
+
+/// Rasterizes tessellated egui meshes in software.
+///
+/// Edges come out anti-aliased because the tessellator "feathers" them: every
+/// outline gets a one pixel wide strip of triangles fading to transparent, and
+/// glyphs carry their coverage in the font atlas. Both end up in the alpha of
+/// the colors handed to `put`, which is expected to composite them.
+///
+/// `texture` has to be the one `mesh.texture_id` points to.
+///
+/// Returns the pixel area that was touched, if any.
+pub fn draw_mesh<F>(
+    mesh: &Mesh,
+    clip_rect: Rect,
+    pixels_per_point: f32,
+    texture: &Texture,
+    mut put: F,
+) -> Option<Rect>
+where
+    F: FnMut(i32, i32, Color32),
+{
+    let clip = Rect::from_min_max(
+        (clip_rect.min.to_vec2() * pixels_per_point).to_pos2(),
+        (clip_rect.max.to_vec2() * pixels_per_point).to_pos2(),
+    );
+    let mut dirty: Option<Rect> = None;
+
+    for triangle in mesh.indices.chunks_exact(3) {
+        let vertex = |i: u32| {
+            let mut v: Vertex = mesh.vertices[i as usize];
+            v.pos = (v.pos.to_vec2() * pixels_per_point).to_pos2();
+            v
+        };
+        let touched = draw_triangle(
+            [vertex(triangle[0]), vertex(triangle[1]), vertex(triangle[2])],
+            clip,
+            texture,
+            &mut put,
+        );
+        if let Some(touched) = touched {
+            dirty = Some(dirty.map_or(touched, |d| d.union(touched)));
+        }
+    }
+
+    dirty
+}
+
+fn draw_triangle<F>(
+    mut v: [Vertex; 3],
+    clip: Rect,
+    texture: &Texture,
+    put: &mut F,
+) -> Option<Rect>
+where
+    F: FnMut(i32, i32, Color32),
+{
+    let mut area = edge(v[0].pos, v[1].pos, v[2].pos);
+    if area.abs() < f32::EPSILON {
+        return None;
+    }
+    // Keep everything clockwise (on screen) so the fill rule below holds
+    if area < 0.0 {
+        v.swap(1, 2);
+        area = -area;
+    }
+
+    let bounds = Rect::from_points(&[v[0].pos, v[1].pos, v[2].pos]).intersect(clip);
+    if !bounds.is_positive() {
+        return None;
+    }
+    let x_start = bounds.min.x.floor() as i32;
+    let y_start = bounds.min.y.floor() as i32;
+    let x_end = bounds.max.x.ceil() as i32;
+    let y_end = bounds.max.y.ceil() as i32;
+
+    let mut touched = false;
+    for y in y_start..y_end {
+        for x in x_start..x_end {
+            let p = Pos2::new(x as f32 + 0.5, y as f32 + 0.5);
+            if !clip.contains(p) {
+                continue;
+            }
+            let w0 = edge(v[1].pos, v[2].pos, p);
+            let w1 = edge(v[2].pos, v[0].pos, p);
+            let w2 = edge(v[0].pos, v[1].pos, p);
+            if !covers(w0, v[1].pos, v[2].pos)
+                || !covers(w1, v[2].pos, v[0].pos)
+                || !covers(w2, v[0].pos, v[1].pos)
+            {
+                continue;
+            }
+
+            let weights = [w0 / area, w1 / area, w2 / area];
+            let uv = Pos2::new(
+                weights[0] * v[0].uv.x + weights[1] * v[1].uv.x + weights[2] * v[2].uv.x,
+                weights[0] * v[0].uv.y + weights[1] * v[1].uv.y + weights[2] * v[2].uv.y,
+            );
+            let texel = texture.sample(uv);
+            if texel.a() == 0 {
+                continue;
+            }
+
+            // Both are premultiplied, so this is what a fragment shader would do
+            let channel = |c: usize| {
+                let value = weights[0] * v[0].color[c] as f32
+                    + weights[1] * v[1].color[c] as f32
+                    + weights[2] * v[2].color[c] as f32;
+                (value * texel[c] as f32 / 255.0).round().clamp(0.0, 255.0) as u8
+            };
+            let color =
+                Color32::from_rgba_premultiplied(channel(0), channel(1), channel(2), channel(3));
+            if color.a() == 0 {
+                continue;
+            }
+            put(x, y, color);
+            touched = true;
+        }
+    }
+
+    if touched {
+        Some(Rect::from_min_max(
+            Pos2::new(x_start as f32, y_start as f32),
+            Pos2::new(x_end as f32, y_end as f32),
+        ))
+    } else {
+        None
+    }
+}
+
+/// Twice the signed area of the triangle `a`, `b`, `p`
+fn edge(a: Pos2, b: Pos2, p: Pos2) -> f32 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+/// Top-left fill rule, so pixels on an edge shared by two triangles are only drawn once.
+/// Otherwise the feathered edges would get blended twice.
+fn covers(weight: f32, a: Pos2, b: Pos2) -> bool {
+    if weight != 0.0 {
+        return weight > 0.0;
+    }
+    let dy = b.y - a.y;
+    let dx = b.x - a.x;
+    (dy == 0.0 && dx > 0.0) || dy < 0.0
+}
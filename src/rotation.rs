@@ -0,0 +1,134 @@
+use egui::{Context, Id, Pos2};
+use fbink_sys::FBInkState;
+
+/// Clockwise rotation of what egui draws, relative to how the framebuffer is laid out
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rotation {
+    #[default]
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+impl Rotation {
+    pub fn swaps_axes(self) -> bool {
+        matches!(self, Rotation::Deg90 | Rotation::Deg270)
+    }
+
+    /// Size egui sees for a framebuffer of `[width, height]`
+    pub fn logical_size(self, [width, height]: [usize; 2]) -> [usize; 2] {
+        if self.swaps_axes() {
+            [height, width]
+        } else {
+            [width, height]
+        }
+    }
+
+    /// Where pixel `[x, y]` of a `[width, height]` logical screen lands in the framebuffer
+    pub fn to_physical(self, [x, y]: [usize; 2], [width, height]: [usize; 2]) -> [usize; 2] {
+        match self {
+            Rotation::Deg0 => [x, y],
+            Rotation::Deg90 => [height - 1 - y, x],
+            Rotation::Deg180 => [width - 1 - x, height - 1 - y],
+            Rotation::Deg270 => [y, width - 1 - x],
+        }
+    }
+
+    /// Same as `to_physical` for the pixel area `[left, top, right, bottom)`
+    pub fn area_to_physical(
+        self,
+        [left, top, right, bottom]: [usize; 4],
+        size: [usize; 2],
+    ) -> [usize; 4] {
+        let [x0, y0] = self.to_physical([left, top], size);
+        let [x1, y1] = self.to_physical([right - 1, bottom - 1], size);
+        [x0.min(x1), y0.min(y1), x0.max(x1) + 1, y0.max(y1) + 1]
+    }
+
+    /// Maps a position in a `[width, height]` framebuffer back to the logical screen
+    pub fn to_logical(self, pos: Pos2, [width, height]: [f32; 2]) -> Pos2 {
+        match self {
+            Rotation::Deg0 => pos,
+            Rotation::Deg90 => Pos2::new(pos.y, width - pos.x),
+            Rotation::Deg180 => Pos2::new(width - pos.x, height - pos.y),
+            Rotation::Deg270 => Pos2::new(height - pos.y, pos.x),
+        }
+    }
+}
+
+/// Turns raw touch panel coordinates into logical screen pixels
+#[derive(Clone, Copy, Debug)]
+pub struct TouchTransform {
+    pub swap_axes: bool,
+    pub mirror_x: bool,
+    pub mirror_y: bool,
+    pub rotation: Rotation,
+    /// Framebuffer size, before rotation
+    pub size: [f32; 2],
+}
+
+impl TouchTransform {
+    pub fn new(state: &FBInkState, rotation: Rotation) -> Self {
+        Self {
+            swap_axes: state.touch_swap_axes,
+            mirror_x: state.touch_mirror_x,
+            mirror_y: state.touch_mirror_y,
+            rotation,
+            size: [state.screen_width as f32, state.screen_height as f32],
+        }
+    }
+
+    pub fn apply(&self, raw: Pos2) -> Pos2 {
+        self.framebuffer_to_logical(self.to_framebuffer(raw))
+    }
+
+    /// Where `raw` is in the framebuffer, before rotation. `raw` goes from 0 to 1 along
+    /// each axis of the panel, so it's scaled to the framebuffer as it is now
+    pub fn to_framebuffer(&self, raw: Pos2) -> Pos2 {
+        let [width, height] = self.size;
+        // FBInk's flags describe the panel relative to the framebuffer, swap first then mirror
+        let mut pos = if self.swap_axes {
+            Pos2::new(raw.y * width, raw.x * height)
+        } else {
+            Pos2::new(raw.x * width, raw.y * height)
+        };
+        if self.mirror_x {
+            pos.x = self.size[0] - 1.0 - pos.x;
+        }
+        if self.mirror_y {
+            pos.y = self.size[1] - 1.0 - pos.y;
+        }
+        pos
+    }
+
+    pub fn framebuffer_to_logical(&self, pos: Pos2) -> Pos2 {
+        self.rotation.to_logical(pos, self.size)
+    }
+}
+
+fn requested_id() -> Id {
+    Id::new("egui_fbink_requested_rotation")
+}
+
+fn current_id() -> Id {
+    Id::new("egui_fbink_rotation")
+}
+
+/// Rotates the screen, starting with the next frame
+pub fn set_rotation(ctx: &Context, rotation: Rotation) {
+    ctx.data_mut(|data| data.insert_temp(requested_id(), rotation));
+}
+
+/// The rotation the screen is drawn with
+pub fn rotation(ctx: &Context) -> Rotation {
+    ctx.data(|data| data.get_temp(current_id()).unwrap_or_default())
+}
+
+pub(crate) fn take_request(ctx: &Context) -> Option<Rotation> {
+    ctx.data_mut(|data| data.remove_temp(requested_id()))
+}
+
+pub(crate) fn store_current(ctx: &Context, rotation: Rotation) {
+    ctx.data_mut(|data| data.insert_temp(current_id(), rotation));
+}
@@ -0,0 +1,145 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use egui::{Context, Id};
+use image::imageops::FilterType;
+use log::{debug, error, warn};
+
+use crate::input::{find_device, InputDevice, EV_KEY, KEY_POWER};
+
+#[derive(Clone, Debug, Default)]
+pub struct SuspendOptions {
+    /// The power button's input device. Found through its capabilities if not set
+    pub power_button: Option<PathBuf>,
+    /// Shown while sleeping, scaled to fit the screen. Without it the app draws the sleep
+    /// screen itself, when `sleeping` is true
+    pub cover: Option<PathBuf>,
+    /// Suspends the device through `/sys/power/state` once the sleep screen is shown.
+    /// Otherwise something else, like the system's power manager, has to
+    pub write_power_state: bool,
+}
+
+fn requested_id() -> Id {
+    Id::new("egui_fbink_requested_sleep")
+}
+
+fn current_id() -> Id {
+    Id::new("egui_fbink_sleeping")
+}
+
+/// Shows the sleep screen, starting with the next frame
+pub fn suspend(ctx: &Context) {
+    ctx.data_mut(|data| data.insert_temp(requested_id(), true));
+    ctx.request_repaint();
+}
+
+/// Leaves the sleep screen and redraws the app
+pub fn resume(ctx: &Context) {
+    ctx.data_mut(|data| data.insert_temp(requested_id(), false));
+    ctx.request_repaint();
+}
+
+/// Whether the sleep screen is up, the app draws it if there is no cover
+pub fn sleeping(ctx: &Context) -> bool {
+    ctx.data(|data| data.get_temp(current_id()).unwrap_or(false))
+}
+
+pub(crate) fn take_request(ctx: &Context) -> Option<bool> {
+    ctx.data_mut(|data| data.remove_temp(requested_id()))
+}
+
+pub(crate) fn store_current(ctx: &Context, sleeping: bool) {
+    ctx.data_mut(|data| data.insert_temp(current_id(), sleeping));
+}
+
+/// Toggles the sleep screen when the power button is pressed, in a background thread
+pub(crate) fn spawn(ctx: Context, device: Option<PathBuf>) {
+    let Some(path) = device.or_else(|| find_device("key", KEY_POWER)) else {
+        warn!("No power button found");
+        return;
+    };
+    let mut device = match InputDevice::open(&path) {
+        Ok(device) => device,
+        Err(err) => {
+            error!("Failed to open power button {}: {}", path.display(), err);
+            return;
+        }
+    };
+
+    thread::Builder::new()
+        .name("power-button".to_owned())
+        .spawn(move || loop {
+            if let Err(err) = device.wait(None) {
+                error!("Failed to wait for {}: {}", device.path().display(), err);
+                return;
+            }
+            let events = match device.read_events() {
+                Ok(events) => events,
+                Err(err) => {
+                    error!("Failed to read {}: {}", device.path().display(), err);
+                    return;
+                }
+            };
+            // Acted on when pressed, releasing it or holding it down does nothing
+            let presses = events
+                .iter()
+                .filter(|event| {
+                    event.type_ == EV_KEY && event.code == KEY_POWER && event.value == 1
+                })
+                .count();
+            if presses % 2 == 1 {
+                if sleeping(&ctx) {
+                    debug!("Power button pressed, waking up");
+                    resume(&ctx);
+                } else {
+                    debug!("Power button pressed, going to sleep");
+                    suspend(&ctx);
+                }
+            }
+        })
+        .expect("Failed to spawn the power button thread");
+    debug!("Watching the power button at {}", path.display());
+}
+
+/// The cover in 8-bit gray, scaled to fit `size` and centered on white
+pub(crate) fn load_cover(path: &Path, [width, height]: [usize; 2]) -> Option<Vec<u8>> {
+    let image = match image::open(path) {
+        Ok(image) => image,
+        Err(err) => {
+            warn!("Failed to load the cover {}: {}", path.display(), err);
+            return None;
+        }
+    };
+    let image = image
+        .resize(width as u32, height as u32, FilterType::Triangle)
+        .into_luma8();
+    let (left, top) = (
+        (width - image.width() as usize) / 2,
+        (height - image.height() as usize) / 2,
+    );
+    let mut pixels = vec![255; width * height];
+    for (x, y, pixel) in image.enumerate_pixels() {
+        pixels[(top + y as usize) * width + left + x as usize] = pixel.0[0];
+    }
+    Some(pixels)
+}
+
+/// Suspends to RAM. Only returns once the device is awake again
+pub(crate) fn enter_power_state(sysfs_root: &Path) -> io::Result<()> {
+    // Kobos want this set around a suspend, or they only half sleep
+    let extended = sysfs_root.join("power/state-extended");
+    let has_extended = extended.exists();
+    if has_extended {
+        fs::write(&extended, "1")?;
+    }
+    debug!("Suspending");
+    let result = fs::write(sysfs_root.join("power/state"), "mem");
+    if has_extended {
+        if let Err(err) = fs::write(&extended, "0") {
+            warn!("Failed to reset {}: {}", extended.display(), err);
+        }
+    }
+    result
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use egui::epaint::textures::{TextureFilter, TextureOptions, TextureWrapMode};
+use egui::epaint::{ImageData, ImageDelta};
+use egui::{Color32, Pos2, TextureId};
+use log::{debug, warn};
+
+/// An egui texture kept in memory, in premultiplied sRGBA like egui gives it to us
+pub struct Texture {
+    pub size: [usize; 2],
+    pub pixels: Vec<Color32>,
+    pub options: TextureOptions,
+}
+
+impl Texture {
+    fn new(image: &ImageData, options: TextureOptions) -> Self {
+        Self {
+            size: image.size(),
+            pixels: image_pixels(image),
+            options,
+        }
+    }
+
+    /// Samples the texture at normalized coordinates, like a GPU would.
+    /// Without mipmaps there is no telling minification apart, so the magnification filter is
+    /// always used
+    pub fn sample(&self, uv: Pos2) -> Color32 {
+        let [width, height] = self.size;
+        if width == 0 || height == 0 {
+            return Color32::TRANSPARENT;
+        }
+        let x = uv.x * width as f32 - 0.5;
+        let y = uv.y * height as f32 - 0.5;
+        match self.options.magnification {
+            TextureFilter::Nearest => self.texel(x.round() as isize, y.round() as isize),
+            TextureFilter::Linear => {
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as isize, y0 as isize);
+                let top = lerp(self.texel(x0, y0), self.texel(x0 + 1, y0), fx);
+                let bottom = lerp(self.texel(x0, y0 + 1), self.texel(x0 + 1, y0 + 1), fx);
+                lerp(top, bottom, fy)
+            }
+        }
+    }
+
+    fn texel(&self, x: isize, y: isize) -> Color32 {
+        let [width, height] = self.size;
+        let x = wrap(x, width, self.options.wrap_mode);
+        let y = wrap(y, height, self.options.wrap_mode);
+        self.pixels[y * width + x]
+    }
+}
+
+/// Keeps the textures egui asks for through `TexturesDelta`, including the font atlas
+#[derive(Default)]
+pub struct TextureManager {
+    textures: HashMap<TextureId, Texture>,
+}
+
+impl TextureManager {
+    pub fn set(&mut self, id: TextureId, delta: ImageDelta) {
+        let Some([x, y]) = delta.pos else {
+            debug!("Setting texture {:?} of size {:?}", id, delta.image.size());
+            self.textures.insert(id, Texture::new(&delta.image, delta.options));
+            return;
+        };
+
+        let Some(texture) = self.textures.get_mut(&id) else {
+            warn!("Partial update of texture {:?} that doesn't exist", id);
+            return;
+        };
+        let [width, height] = delta.image.size();
+        if x + width > texture.size[0] || y + height > texture.size[1] {
+            warn!("Partial update of texture {:?} is out of its bounds", id);
+            return;
+        }
+        let pixels = image_pixels(&delta.image);
+        for row in 0..height {
+            let start = (y + row) * texture.size[0] + x;
+            texture.pixels[start..start + width]
+                .copy_from_slice(&pixels[row * width..(row + 1) * width]);
+        }
+        texture.options = delta.options;
+    }
+
+    pub fn free(&mut self, id: TextureId) {
+        debug!("Freeing texture {:?}", id);
+        self.textures.remove(&id);
+    }
+
+    pub fn get(&self, id: TextureId) -> Option<&Texture> {
+        self.textures.get(&id)
+    }
+}
+
+fn image_pixels(image: &ImageData) -> Vec<Color32> {
+    match image {
+        ImageData::Color(image) => image.pixels.clone(),
+        ImageData::Font(image) => image.srgba_pixels(None).collect(),
+    }
+}
+
+fn wrap(i: isize, size: usize, mode: TextureWrapMode) -> usize {
+    let size = size as isize;
+    let i = match mode {
+        TextureWrapMode::ClampToEdge => i.clamp(0, size - 1),
+        TextureWrapMode::Repeat => i.rem_euclid(size),
+        TextureWrapMode::MirroredRepeat => {
+            let i = i.rem_euclid(2 * size);
+            if i < size {
+                i
+            } else {
+                2 * size - 1 - i
+            }
+        }
+    };
+    i as usize
+}
+
+fn lerp(a: Color32, b: Color32, t: f32) -> Color32 {
+    let channel = |i: usize| (a[i] as f32 + (b[i] as f32 - a[i] as f32) * t).round() as u8;
+    Color32::from_rgba_premultiplied(channel(0), channel(1), channel(2), channel(3))
+}
@@ -0,0 +1,163 @@
+use egui::epaint::Shadow;
+use egui::style::WidgetVisuals;
+use egui::{Color32, Context, Id, Rounding, Stroke, Style, Vec2};
+
+use crate::eink_theme;
+
+/// The themes that come with the crate. Every gray they use is one of the 16 levels
+/// the panel can show, so nothing vanishes or bands once a frame is quantized
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Theme {
+    /// The egui-themer one the crate always had, with its grays snapped to the 16 levels
+    #[default]
+    Default,
+    /// Black on white only, crisp even with fast waveforms
+    HighContrast,
+    /// Grays instead of black, calmer for long reading
+    SoftGray,
+    /// High contrast with big text and big widgets
+    Accessibility,
+}
+
+impl Theme {
+    pub fn style(self) -> Style {
+        match self {
+            Theme::Default => eink_theme::style(),
+            Theme::HighContrast => high_contrast(),
+            Theme::SoftGray => soft_gray(),
+            Theme::Accessibility => accessibility(),
+        }
+    }
+}
+
+fn requested_id() -> Id {
+    Id::new("egui_fbink_requested_theme")
+}
+
+fn current_id() -> Id {
+    Id::new("egui_fbink_theme")
+}
+
+/// Switches the theme, starting with the next frame. Zoom and night mode stay as they are
+pub fn set_theme(ctx: &Context, theme: Theme) {
+    ctx.data_mut(|data| data.insert_temp(requested_id(), theme));
+}
+
+pub fn theme(ctx: &Context) -> Theme {
+    ctx.data(|data| data.get_temp(current_id()).unwrap_or_default())
+}
+
+pub(crate) fn take_request(ctx: &Context) -> Option<Theme> {
+    ctx.data_mut(|data| data.remove_temp(requested_id()))
+}
+
+pub(crate) fn store_current(ctx: &Context, theme: Theme) {
+    ctx.data_mut(|data| data.insert_temp(current_id(), theme));
+}
+
+/// One of the 16 grays, 0 is black and 15 white
+const fn level(n: u8) -> Color32 {
+    Color32::from_gray(n * 17)
+}
+
+fn widget(bg: Color32, weak_bg: Color32, stroke: Stroke, fg: Stroke) -> WidgetVisuals {
+    WidgetVisuals {
+        bg_fill: bg,
+        weak_bg_fill: weak_bg,
+        bg_stroke: stroke,
+        fg_stroke: fg,
+        rounding: Rounding::same(3.0),
+        expansion: 0.0,
+    }
+}
+
+fn high_contrast() -> Style {
+    let mut style = eink_theme::style();
+    let black = Stroke::new(2.0, level(0));
+    let white = Stroke::new(2.0, level(15));
+
+    let visuals = &mut style.visuals;
+    visuals.override_text_color = Some(level(0));
+    visuals.widgets.noninteractive = widget(level(15), level(15), Stroke::new(1.0, level(0)), black);
+    visuals.widgets.inactive = widget(level(15), level(15), black, black);
+    // Fingers don't hover, this mostly shows up while a pen is close
+    visuals.widgets.hovered = widget(level(15), level(15), black, black);
+    // Pressed widgets are inverted, the one change A2 refreshes show well
+    visuals.widgets.active = widget(level(0), level(0), black, white);
+    visuals.widgets.open = widget(level(12), level(12), black, black);
+    visuals.selection.bg_fill = level(0);
+    visuals.selection.stroke = white;
+    visuals.hyperlink_color = level(0);
+    visuals.faint_bg_color = level(13);
+    visuals.extreme_bg_color = level(15);
+    visuals.code_bg_color = level(13);
+    visuals.warn_fg_color = level(0);
+    visuals.error_fg_color = level(0);
+    visuals.window_fill = level(15);
+    visuals.window_stroke = black;
+    visuals.panel_fill = level(15);
+    // Soft shadows turn into dither noise
+    visuals.window_shadow = Shadow::NONE;
+    visuals.popup_shadow = Shadow::NONE;
+    style
+}
+
+fn soft_gray() -> Style {
+    let mut style = eink_theme::style();
+    let stroke = Stroke::new(1.5, level(7));
+    let text = Stroke::new(1.5, level(3));
+
+    let visuals = &mut style.visuals;
+    visuals.override_text_color = Some(level(3));
+    visuals.widgets.noninteractive = widget(level(14), level(14), Stroke::new(1.0, level(9)), text);
+    visuals.widgets.inactive = widget(level(12), level(13), stroke, text);
+    visuals.widgets.hovered = widget(level(11), level(12), stroke, text);
+    visuals.widgets.active = widget(level(9), level(9), Stroke::new(1.5, level(3)), text);
+    visuals.widgets.open = widget(level(12), level(12), stroke, text);
+    visuals.selection.bg_fill = level(10);
+    visuals.selection.stroke = Stroke::new(1.5, level(0));
+    visuals.hyperlink_color = level(5);
+    visuals.faint_bg_color = level(14);
+    visuals.extreme_bg_color = level(15);
+    visuals.code_bg_color = level(13);
+    visuals.warn_fg_color = level(4);
+    visuals.error_fg_color = level(1);
+    visuals.window_fill = level(15);
+    visuals.window_stroke = stroke;
+    visuals.panel_fill = level(14);
+    visuals.window_shadow = Shadow::NONE;
+    visuals.popup_shadow = Shadow::NONE;
+    style
+}
+
+fn accessibility() -> Style {
+    let mut style = high_contrast();
+    for font in style.text_styles.values_mut() {
+        font.size *= 1.6;
+    }
+
+    let spacing = &mut style.spacing;
+    spacing.item_spacing = Vec2::new(14.0, 12.0);
+    spacing.button_padding = Vec2::new(12.0, 8.0);
+    spacing.interact_size = Vec2::new(64.0, 44.0);
+    spacing.icon_width = 28.0;
+    spacing.icon_width_inner = 16.0;
+    spacing.icon_spacing = 10.0;
+    spacing.slider_width = 240.0;
+    spacing.indent = 28.0;
+
+    let visuals = &mut style.visuals;
+    for widget in [
+        &mut visuals.widgets.noninteractive,
+        &mut visuals.widgets.inactive,
+        &mut visuals.widgets.hovered,
+        &mut visuals.widgets.active,
+        &mut visuals.widgets.open,
+    ] {
+        widget.bg_stroke.width = 3.0;
+        widget.fg_stroke.width = 3.0;
+    }
+    visuals.selection.stroke.width = 3.0;
+    visuals.window_stroke.width = 3.0;
+    style
+}
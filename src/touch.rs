@@ -0,0 +1,182 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use egui::{Context, Pos2, TouchPhase};
+use log::{debug, error, warn};
+
+use crate::input::{
+    device_has, find_device, find_devices, Axis, InputDevice, ABS_MT_POSITION_X, ABS_MT_POSITION_Y,
+    ABS_MT_SLOT, ABS_MT_TRACKING_ID, ABS_X, ABS_Y, BTN_TOOL_PEN, BTN_TOUCH, EV_ABS, EV_KEY, EV_SYN,
+    SYN_REPORT,
+};
+use crate::rotation::TouchTransform;
+
+/// A finger going down, moving or going up, from 0 to 1 along each axis of the panel
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct RawTouch {
+    pub id: u64,
+    pub phase: TouchPhase,
+    pub pos: Pos2,
+}
+
+/// Touches read by the background thread, waiting for the next frame
+pub(crate) type TouchQueue = Arc<Mutex<Vec<RawTouch>>>;
+
+#[derive(Clone, Copy, Default)]
+struct Slot {
+    id: Option<u64>,
+    pos: Pos2,
+    started: bool,
+    moved: bool,
+    lifted: bool,
+}
+
+/// Reads the touch panel in a background thread. `transform` is only used for panels that
+/// don't report their range, the framebuffer may change size later so the rest is applied
+/// when the touches are handed to egui
+pub(crate) fn spawn(ctx: Context, device: Option<PathBuf>, transform: TouchTransform) -> Option<TouchQueue> {
+    let Some(path) = device
+        .or_else(|| find_device("abs", ABS_MT_POSITION_X))
+        // A stylus digitizer has the same axes, but it's the pen's
+        .or_else(|| {
+            find_devices("abs", ABS_X)
+                .into_iter()
+                .find(|path| !device_has(path, "key", BTN_TOOL_PEN))
+        })
+    else {
+        warn!("No touch panel found");
+        return None;
+    };
+    let mut device = match InputDevice::open(&path) {
+        Ok(device) => device,
+        Err(err) => {
+            error!("Failed to open touch panel {}: {}", path.display(), err);
+            return None;
+        }
+    };
+
+    // With swapped axes the panel's x runs along the framebuffer's height
+    let [width, height] = transform.size;
+    let (x_size, y_size) = if transform.swap_axes {
+        (height, width)
+    } else {
+        (width, height)
+    };
+    // Multitouch panels also send ABS_X and ABS_Y for the first contact, to emulate a
+    // pointer. Those would land in whatever slot is current, so only one pair is read
+    let (x_code, y_code) = if Axis::new(&device, &[ABS_MT_POSITION_X], 1.0).is_some() {
+        (ABS_MT_POSITION_X, ABS_MT_POSITION_Y)
+    } else {
+        (ABS_X, ABS_Y)
+    };
+    let x_axis = Axis::new(&device, &[x_code], 1.0).or(Some(Axis::assumed(x_size)));
+    let y_axis = Axis::new(&device, &[y_code], 1.0).or(Some(Axis::assumed(y_size)));
+
+    let queue = TouchQueue::default();
+    let thread_queue = queue.clone();
+    thread::Builder::new()
+        .name("touch".to_owned())
+        .spawn(move || {
+            let mut slots: BTreeMap<i32, Slot> = BTreeMap::new();
+            let mut slot = 0;
+            // Protocol A panels and single touch ones only have one contact, without ids
+            let mut next_id = 0;
+            loop {
+                if let Err(err) = device.wait(None) {
+                    error!("Failed to wait for {}: {}", device.path().display(), err);
+                    return;
+                }
+                let events = match device.read_events() {
+                    Ok(events) => events,
+                    Err(err) => {
+                        error!("Failed to read {}: {}", device.path().display(), err);
+                        return;
+                    }
+                };
+                for event in events {
+                    match (event.type_, event.code) {
+                        (EV_ABS, ABS_MT_SLOT) => slot = event.value,
+                        (EV_ABS, ABS_MT_TRACKING_ID) => {
+                            let current = slots.entry(slot).or_default();
+                            if event.value < 0 {
+                                current.lifted = true;
+                            } else {
+                                current.id = Some(event.value as u64);
+                                current.started = true;
+                            }
+                        }
+                        (EV_ABS, code) if code == x_code => {
+                            let current = slots.entry(slot).or_default();
+                            current.pos.x = Axis::scale(x_axis, event.value);
+                            current.moved = true;
+                        }
+                        (EV_ABS, code) if code == y_code => {
+                            let current = slots.entry(slot).or_default();
+                            current.pos.y = Axis::scale(y_axis, event.value);
+                            current.moved = true;
+                        }
+                        (EV_KEY, BTN_TOUCH) => {
+                            let current = slots.entry(slot).or_default();
+                            if event.value == 0 {
+                                current.lifted = true;
+                            } else if current.id.is_none() {
+                                next_id += 1;
+                                current.id = Some(next_id);
+                                current.started = true;
+                            }
+                        }
+                        (EV_SYN, SYN_REPORT) => {
+                            let touches = report(&mut slots);
+                            if !touches.is_empty() {
+                                thread_queue.lock().unwrap().extend(touches);
+                                ctx.request_repaint();
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        })
+        .expect("Failed to spawn the touch thread");
+
+    debug!("Reading touches from {}", path.display());
+    Some(queue)
+}
+
+/// What changed in the slots since the last report
+fn report(slots: &mut BTreeMap<i32, Slot>) -> Vec<RawTouch> {
+    let mut touches = Vec::new();
+    for slot in slots.values_mut() {
+        let Some(id) = slot.id else {
+            continue;
+        };
+        let phase = if slot.started {
+            Some(TouchPhase::Start)
+        } else if slot.moved {
+            Some(TouchPhase::Move)
+        } else {
+            None
+        };
+        if let Some(phase) = phase {
+            touches.push(RawTouch {
+                id,
+                phase,
+                pos: slot.pos,
+            });
+        }
+        if slot.lifted {
+            touches.push(RawTouch {
+                id,
+                phase: TouchPhase::End,
+                pos: slot.pos,
+            });
+            slot.id = None;
+        }
+        slot.started = false;
+        slot.moved = false;
+        slot.lifted = false;
+    }
+    touches
+}
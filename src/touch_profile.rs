@@ -0,0 +1,61 @@
+use egui::{Context, Id, Style, Vec2};
+
+/// Sizes widgets for fingers instead of a mouse. It only ever grows things, so it goes
+/// on top of any theme and zoom
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TouchProfile {
+    /// Smallest side of anything that can be tapped, in millimeters
+    pub min_target_mm: f32,
+    /// How far from a widget a tap still hits it, in millimeters
+    pub interact_radius_mm: f32,
+}
+
+impl Default for TouchProfile {
+    fn default() -> Self {
+        Self {
+            // Around a fingertip, what most touch guidelines ask for
+            min_target_mm: 8.0,
+            interact_radius_mm: 2.5,
+        }
+    }
+}
+
+impl TouchProfile {
+    /// `dpi` is the one of the panel, `pixels_per_point` whatever egui draws with
+    pub fn apply(&self, style: &mut Style, dpi: f32, pixels_per_point: f32) {
+        let points = |mm: f32| mm / 25.4 * dpi / pixels_per_point;
+        let target = points(self.min_target_mm);
+
+        let spacing = &mut style.spacing;
+        // Sliders size their handle from the height they are given
+        spacing.interact_size = spacing.interact_size.max(Vec2::splat(target));
+        let icon_width = spacing.icon_width.max(target * 0.6);
+        // Keep the theme's proportions between the box and its check mark
+        spacing.icon_width_inner *= icon_width / spacing.icon_width;
+        spacing.icon_width = icon_width;
+        spacing.icon_spacing = spacing.icon_spacing.max(target * 0.2);
+        spacing.slider_rail_height = spacing.slider_rail_height.max(target * 0.2);
+        spacing.item_spacing.y = spacing.item_spacing.y.max(target * 0.15);
+
+        let interaction = &mut style.interaction;
+        interaction.interact_radius = interaction
+            .interact_radius
+            .max(points(self.interact_radius_mm));
+        interaction.resize_grab_radius_side = interaction.resize_grab_radius_side.max(target / 2.0);
+        interaction.resize_grab_radius_corner =
+            interaction.resize_grab_radius_corner.max(target / 2.0);
+    }
+}
+
+fn requested_id() -> Id {
+    Id::new("egui_fbink_requested_touch_profile")
+}
+
+/// Changes the touch profile, or turns it off with `None`, starting with the next frame
+pub fn set_touch_profile(ctx: &Context, profile: Option<TouchProfile>) {
+    ctx.data_mut(|data| data.insert_temp(requested_id(), profile));
+}
+
+pub(crate) fn take_request(ctx: &Context) -> Option<Option<TouchProfile>> {
+    ctx.data_mut(|data| data.remove_temp(requested_id()))
+}
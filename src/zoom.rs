@@ -0,0 +1,27 @@
+use egui::{Context, Id};
+
+fn requested_id() -> Id {
+    Id::new("egui_fbink_requested_zoom")
+}
+
+fn current_id() -> Id {
+    Id::new("egui_fbink_zoom")
+}
+
+/// Zooms the whole UI, starting with the next frame. It always scales the theme as it was
+/// designed, so it can be set as often as wanted, from a settings slider for example
+pub fn set_zoom(ctx: &Context, zoom_factor: f32) {
+    ctx.data_mut(|data| data.insert_temp(requested_id(), zoom_factor));
+}
+
+pub fn zoom(ctx: &Context) -> f32 {
+    ctx.data(|data| data.get_temp(current_id()).unwrap_or(1.0))
+}
+
+pub(crate) fn take_request(ctx: &Context) -> Option<f32> {
+    ctx.data_mut(|data| data.remove_temp(requested_id()))
+}
+
+pub(crate) fn store_current(ctx: &Context, zoom_factor: f32) {
+    ctx.data_mut(|data| data.insert_temp(current_id(), zoom_factor));
+}